@@ -0,0 +1,514 @@
+//! Password health audit
+//!
+//! Scores every decrypted password for strength, groups reused passwords,
+//! flags passwords that have not been changed for a long time and logins
+//! submitted over plain HTTP. Passwords are only compared through their
+//! SHA-256 hashes and never appear in the report.
+
+use crate::browser::Browser;
+use crate::models::LoginInfoWithProfile;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Default age after which a password is reported as old
+pub const DEFAULT_MAX_AGE_DAYS: u32 = 365;
+
+/// Passwords scoring below this are reported as weak
+pub const WEAK_SCORE_THRESHOLD: u8 = 2;
+
+/// Most common passwords (and their base words), checked after undoing
+/// common character substitutions
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "123456", "12345678", "qwerty", "abc123", "monkey", "letmein", "dragon", "111111",
+    "baseball", "iloveyou", "trustno1", "sunshine", "master", "welcome", "shadow", "ashley",
+    "football", "jesus", "michael", "ninja", "mustang", "admin", "login", "princess", "starwars",
+    "whatever", "freedom", "passw0rd", "hello", "charlie", "secret", "summer", "winter", "spring",
+    "autumn", "flower", "hunter", "killer", "pokemon", "superman", "batman", "computer",
+    "internet", "google", "chrome", "changeme", "default", "access", "love", "soccer", "hockey",
+    "ranger", "buster", "tigger", "cookie", "cheese", "pepper", "ginger", "maggie", "jordan",
+    "harley", "robert", "thomas", "daniel",
+];
+
+/// Keyboard rows used to detect runs such as "qwerty" or "asdf"
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Audit settings
+#[derive(Debug, Clone)]
+pub struct AuditOptions {
+    /// Report passwords not modified for more than this many days
+    pub max_age_days: u32,
+    /// Reference time for the age check
+    pub now: DateTime<Utc>,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        Self {
+            max_age_days: DEFAULT_MAX_AGE_DAYS,
+            now: Utc::now(),
+        }
+    }
+}
+
+/// zxcvbn-style strength estimate
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StrengthEstimate {
+    /// 0 (very weak) to 4 (very strong)
+    pub score: u8,
+    /// Estimated entropy after discounting recognized patterns
+    pub entropy_bits: f64,
+    /// Patterns that made the password easier to guess
+    pub feedback: Vec<String>,
+}
+
+/// Score label, as shown in tables
+pub fn score_label(score: u8) -> &'static str {
+    match score {
+        0 => "very weak",
+        1 => "weak",
+        2 => "fair",
+        3 => "strong",
+        _ => "very strong",
+    }
+}
+
+/// Kind of problem found for a login
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FindingKind {
+    /// Low strength score
+    Weak {
+        score: u8,
+        entropy_bits: f64,
+        feedback: Vec<String>,
+    },
+    /// Same password as the other logins of `group`
+    Reused { group: usize, group_size: usize },
+    /// Password not modified for `age_days`
+    Old { age_days: i64 },
+    /// Login page served over plain HTTP
+    InsecureOrigin,
+}
+
+impl FindingKind {
+    /// Short name, as shown in tables
+    pub fn label(&self) -> &'static str {
+        match self {
+            FindingKind::Weak { .. } => "weak",
+            FindingKind::Reused { .. } => "reused",
+            FindingKind::Old { .. } => "old",
+            FindingKind::InsecureOrigin => "insecure origin",
+        }
+    }
+
+    /// Human-readable details, never including the password
+    pub fn details(&self) -> String {
+        match self {
+            FindingKind::Weak {
+                score,
+                entropy_bits,
+                feedback,
+            } => {
+                let mut details = format!("{} (~{:.0} bits)", score_label(*score), entropy_bits);
+                if !feedback.is_empty() {
+                    details.push_str(&format!(": {}", feedback.join(", ")));
+                }
+                details
+            }
+            FindingKind::Reused { group, group_size } => {
+                format!("group #{} ({} logins)", group, group_size)
+            }
+            FindingKind::Old { age_days } => format!("unchanged for {} days", age_days),
+            FindingKind::InsecureOrigin => "submitted over http://".to_string(),
+        }
+    }
+}
+
+/// One problem with one login
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Finding {
+    pub browser: Browser,
+    pub profile: String,
+    pub url: String,
+    pub username: String,
+    #[serde(flatten)]
+    pub kind: FindingKind,
+}
+
+/// Number of logins per finding kind
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct AuditSummary {
+    pub total: usize,
+    pub weak: usize,
+    pub reused: usize,
+    pub reuse_groups: usize,
+    pub old: usize,
+    pub insecure_origin: usize,
+    /// Number of passwords per score (0..=4)
+    pub scores: [usize; 5],
+}
+
+/// Result of an audit
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AuditReport {
+    pub summary: AuditSummary,
+    pub findings: Vec<Finding>,
+}
+
+/// Audit decrypted logins
+pub fn audit_logins(logins: &[LoginInfoWithProfile], options: &AuditOptions) -> AuditReport {
+    let mut summary = AuditSummary {
+        total: logins.len(),
+        ..Default::default()
+    };
+    let mut findings = Vec::new();
+    let finding = |login: &LoginInfoWithProfile, kind: FindingKind| Finding {
+        browser: login.browser,
+        profile: login.profile.clone(),
+        url: login.info.url.clone(),
+        username: login.info.username.clone(),
+        kind,
+    };
+
+    // Strength, age and origin of every login
+    for login in logins {
+        let estimate = estimate_strength(&login.info.password, &user_inputs(login));
+        summary.scores[estimate.score as usize] += 1;
+        if estimate.score < WEAK_SCORE_THRESHOLD {
+            summary.weak += 1;
+            findings.push(finding(
+                login,
+                FindingKind::Weak {
+                    score: estimate.score,
+                    entropy_bits: (estimate.entropy_bits * 10.0).round() / 10.0,
+                    feedback: estimate.feedback,
+                },
+            ));
+        }
+
+        let modified = login
+            .info
+            .date_password_modified
+            .or(login.info.date_created);
+        if let Some(modified) = modified {
+            let age_days = (options.now - modified).num_days();
+            if age_days > i64::from(options.max_age_days) {
+                summary.old += 1;
+                findings.push(finding(login, FindingKind::Old { age_days }));
+            }
+        }
+
+        if is_insecure_origin(&login.info.url) {
+            summary.insecure_origin += 1;
+            findings.push(finding(login, FindingKind::InsecureOrigin));
+        }
+    }
+
+    // Reuse, compared by hash; groups are numbered in order of appearance
+    let mut groups: Vec<Vec<&LoginInfoWithProfile>> = Vec::new();
+    let mut group_by_hash: HashMap<[u8; 32], usize> = HashMap::new();
+    for login in logins {
+        let hash: [u8; 32] = Sha256::digest(login.info.password.as_bytes()).into();
+        let index = *group_by_hash.entry(hash).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(login);
+    }
+    let reused = groups.into_iter().filter(|group| group.len() > 1);
+    for (number, group) in reused.enumerate() {
+        summary.reuse_groups += 1;
+        summary.reused += group.len();
+        for login in &group {
+            findings.push(finding(
+                login,
+                FindingKind::Reused {
+                    group: number + 1,
+                    group_size: group.len(),
+                },
+            ));
+        }
+    }
+
+    AuditReport { summary, findings }
+}
+
+/// Username and site name, which make a password easier to guess if it
+/// contains them
+fn user_inputs(login: &LoginInfoWithProfile) -> Vec<String> {
+    let mut inputs = Vec::new();
+    let username = &login.info.username;
+    inputs.push(username.split('@').next().unwrap_or(username).to_string());
+    if let Ok(url) = url::Url::parse(&login.info.url)
+        && let Some(host) = url.host_str()
+    {
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        inputs.extend(host.split('.').map(str::to_string));
+    }
+    inputs.retain(|input| input.chars().count() >= 3);
+    inputs
+}
+
+/// Whether a login URL is plain HTTP (loopback addresses excepted)
+fn is_insecure_origin(url: &str) -> bool {
+    match url::Url::parse(url) {
+        Ok(url) if url.scheme() == "http" => {
+            !matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"))
+        }
+        _ => false,
+    }
+}
+
+/// Estimate the strength of a password
+///
+/// The password is split into recognized patterns (common passwords and
+/// user inputs, also with "l33t" substitutions undone; repeated characters;
+/// alphabetic, numeric and keyboard sequences; years), each costing only a
+/// few bits, while the remaining characters cost the full character-set
+/// entropy. The total is mapped to a 0-4 score like zxcvbn's.
+pub fn estimate_strength(password: &str, user_inputs: &[String]) -> StrengthEstimate {
+    let chars: Vec<char> = password.chars().collect();
+    let normalized: Vec<char> = chars.iter().map(|c| unleet(*c)).collect();
+    let pool = charset_size(&chars);
+    let mut feedback = Vec::new();
+    let mut entropy = 0.0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match longest_pattern(&chars, &normalized, i, user_inputs) {
+            Some((len, bits, pattern)) => {
+                if !feedback.contains(&pattern) {
+                    feedback.push(pattern);
+                }
+                entropy += bits;
+                i += len;
+            }
+            None => {
+                entropy += pool.log2();
+                i += 1;
+            }
+        }
+    }
+
+    if chars.len() < 8 {
+        feedback.push("shorter than 8 characters".to_string());
+    }
+
+    let score = match entropy {
+        e if e < 20.0 => 0,
+        e if e < 35.0 => 1,
+        e if e < 50.0 => 2,
+        e if e < 70.0 => 3,
+        _ => 4,
+    };
+
+    StrengthEstimate {
+        score,
+        entropy_bits: entropy,
+        feedback,
+    }
+}
+
+/// Longest pattern starting at `start`: (length, entropy bits, description)
+fn longest_pattern(
+    chars: &[char],
+    normalized: &[char],
+    start: usize,
+    user_inputs: &[String],
+) -> Option<(usize, f64, String)> {
+    let rest = &chars[start..];
+    // Words are looked up both as typed ("123456") and un-l33ted ("p@ss" as "pass")
+    let lower = lowercase(rest);
+    let unleeted = lowercase(&normalized[start..]);
+    let matches = |word: &str| lower.starts_with(word) || unleeted.starts_with(word);
+    let mut best: Option<(usize, f64, String)> = None;
+    let mut consider = |len: usize, bits: f64, pattern: &str| {
+        if len >= 3 && best.as_ref().is_none_or(|(best_len, _, _)| len > *best_len) {
+            best = Some((len, bits, pattern.to_string()));
+        }
+    };
+
+    // Dictionary words: common passwords and user inputs
+    for (rank, word) in COMMON_PASSWORDS.iter().enumerate() {
+        if matches(word) {
+            consider(
+                word.chars().count(),
+                ((rank + 1) as f64).log2() + 1.0,
+                "common password",
+            );
+        }
+    }
+    for input in user_inputs {
+        let input = input.to_lowercase();
+        if matches(&input) {
+            consider(input.chars().count(), 2.0, "contains username or site name");
+        }
+    }
+
+    // Repeated character ("aaaa")
+    let repeat = rest.iter().take_while(|c| **c == rest[0]).count();
+    consider(repeat, 4.0 + (repeat as f64).log2(), "repeated characters");
+
+    // Alphabetic / numeric sequences ("abcd", "4321")
+    if rest.len() >= 2 {
+        let step = rest[1] as i64 - rest[0] as i64;
+        if step == 1 || step == -1 {
+            let run = 1 + rest
+                .windows(2)
+                .take_while(|w| w[1] as i64 - w[0] as i64 == step)
+                .count();
+            consider(run, 4.0 + (run as f64).log2(), "sequence");
+        }
+    }
+
+    // Keyboard runs ("qwert", "asdf"), on the keys as typed
+    for row in KEYBOARD_ROWS {
+        let run = (0..row.len())
+            .map(|offset| common_prefix_len(&row[offset..], &lower))
+            .max()
+            .unwrap_or(0);
+        if run >= 4 {
+            consider(run, 5.0 + (run as f64).log2(), "keyboard pattern");
+        }
+    }
+
+    // Years 1900-2099
+    if rest.len() >= 4 {
+        let year: String = rest[..4].iter().collect();
+        if let Ok(year) = year.parse::<u32>()
+            && (1900..2100).contains(&year)
+        {
+            consider(4, 7.6, "year");
+        }
+    }
+
+    best
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
+
+fn lowercase(text: &[char]) -> String {
+    text.iter().flat_map(|c| c.to_lowercase()).collect()
+}
+
+/// Undo common character substitutions ("p@ssw0rd" -> "password")
+fn unleet(c: char) -> char {
+    match c {
+        '@' | '4' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+/// Size of the character classes used by the password
+fn charset_size(chars: &[char]) -> f64 {
+    let mut size = 0.0;
+    if chars.iter().any(char::is_ascii_lowercase) {
+        size += 26.0;
+    }
+    if chars.iter().any(char::is_ascii_uppercase) {
+        size += 26.0;
+    }
+    if chars.iter().any(char::is_ascii_digit) {
+        size += 10.0;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        size += 33.0;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        size += 100.0;
+    }
+    f64::max(size, 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestProfileLogin;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_estimate_strength() {
+        let score = |password: &str| estimate_strength(password, &[]).score;
+        assert_eq!(score("password"), 0);
+        assert_eq!(score("P@ssw0rd1"), 0);
+        assert_eq!(score("qwerty123"), 0);
+        assert_eq!(score("aaaaaaaaaaaa"), 0);
+        assert!(score("Summer2024!") <= 1);
+        assert!(score("correct horse battery staple") >= 3);
+        assert_eq!(score("v9#Lq2@xT!m4Zr&8"), 4);
+
+        let estimate = estimate_strength("alice1990", &["alice".to_string()]);
+        assert!(estimate.score <= 1);
+        assert!(
+            estimate
+                .feedback
+                .contains(&"contains username or site name".to_string())
+        );
+    }
+
+    #[test]
+    fn test_common_passwords_with_digits() {
+        for password in [
+            "123456", "12345678", "111111", "abc123", "trustno1", "passw0rd",
+        ] {
+            let estimate = estimate_strength(password, &[]);
+            assert_eq!(estimate.score, 0, "{}", password);
+            assert!(
+                estimate.feedback.contains(&"common password".to_string()),
+                "{}",
+                password
+            );
+        }
+    }
+
+    #[test]
+    fn test_audit_finds_reuse_age_and_http() {
+        let logins = vec![
+            TestProfileLogin::new("https://a.example/", "alice", "Shared-Secret-42!x").build(),
+            TestProfileLogin::new("https://b.example/", "alice", "Shared-Secret-42!x")
+                .profile("Work")
+                .build(),
+            TestProfileLogin::new("http://intranet.example/", "bob", "123456").build(),
+            TestProfileLogin::new("http://localhost:8080/", "carol", "R4nd0m&Unique#Str1ng")
+                .build(),
+            TestProfileLogin::new("https://old.example/", "dave", "x8#Kq!2vLp9@Wz")
+                .date_password_modified(Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()))
+                .build(),
+        ];
+        let options = AuditOptions {
+            max_age_days: 365,
+            now: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+        };
+
+        let report = audit_logins(&logins, &options);
+        assert_eq!(report.summary.total, 5);
+        assert_eq!(report.summary.weak, 1);
+        assert_eq!(report.summary.reused, 2);
+        assert_eq!(report.summary.reuse_groups, 1);
+        assert_eq!(report.summary.old, 1);
+        assert_eq!(report.summary.insecure_origin, 1);
+
+        let kinds: Vec<(&str, &str)> = report
+            .findings
+            .iter()
+            .map(|f| (f.url.as_str(), f.kind.label()))
+            .collect();
+        assert!(kinds.contains(&("http://intranet.example/", "weak")));
+        assert!(kinds.contains(&("http://intranet.example/", "insecure origin")));
+        assert!(kinds.contains(&("https://old.example/", "old")));
+        assert!(kinds.contains(&("https://b.example/", "reused")));
+
+        // The report never contains a password
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(!json.contains("Shared-Secret-42!x"));
+        assert!(!json.contains("123456"));
+    }
+}
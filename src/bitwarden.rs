@@ -0,0 +1,284 @@
+//! Bitwarden unencrypted JSON export format
+//!
+//! A `folders` list and an `items` list of logins (`type` 1), as written by
+//! Bitwarden's own "JSON" export and accepted by its importer.
+
+use crate::chrome_csv::display_name;
+use crate::export::{profile_folder_name, stable_id};
+use crate::models::{DecryptError, LoginInfo, LoginInfoWithProfile};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Bitwarden item type of a login
+pub const ITEM_TYPE_LOGIN: u8 = 1;
+
+/// Top-level export document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BitwardenExport {
+    pub encrypted: bool,
+    #[serde(default)]
+    pub folders: Vec<BitwardenFolder>,
+    pub items: Vec<BitwardenItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BitwardenFolder {
+    pub id: String,
+    pub name: String,
+}
+
+/// A vault item; only logins are written, other types are skipped on import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BitwardenItem {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    #[serde(rename = "type")]
+    pub item_type: u8,
+    pub name: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub login: Option<BitwardenLogin>,
+    #[serde(default)]
+    pub creation_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub revision_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BitwardenLogin {
+    #[serde(default)]
+    pub uris: Vec<BitwardenUri>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub totp: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BitwardenUri {
+    /// URI match detection; `None` uses the vault's default
+    #[serde(rename = "match", default)]
+    pub match_type: Option<u8>,
+    pub uri: String,
+}
+
+/// Build a Bitwarden export
+///
+/// With `folders`, each profile becomes a folder named like the profile
+/// (non-Chrome profiles get the browser name appended).
+pub fn to_bitwarden(logins: &[LoginInfoWithProfile], folders: bool) -> BitwardenExport {
+    let mut export = BitwardenExport {
+        encrypted: false,
+        folders: Vec::new(),
+        items: Vec::new(),
+    };
+
+    for login in logins {
+        let folder_id = folders.then(|| {
+            let name = profile_folder_name(login.browser, &login.profile);
+            let id = stable_id(name.as_bytes());
+            if !export.folders.iter().any(|folder| folder.id == id) {
+                export.folders.push(BitwardenFolder {
+                    id: id.clone(),
+                    name,
+                });
+            }
+            id
+        });
+        export.items.push(to_item(&login.info, folder_id));
+    }
+
+    export
+}
+
+fn to_item(info: &LoginInfo, folder_id: Option<String>) -> BitwardenItem {
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    BitwardenItem {
+        id: None,
+        folder_id,
+        item_type: ITEM_TYPE_LOGIN,
+        name: display_name(&info.url),
+        notes: non_empty(&info.note),
+        favorite: false,
+        login: Some(BitwardenLogin {
+            uris: vec![BitwardenUri {
+                match_type: None,
+                uri: info.url.clone(),
+            }],
+            username: non_empty(&info.username),
+            password: non_empty(&info.password),
+            totp: None,
+        }),
+        creation_date: info.date_created,
+        revision_date: info.date_password_modified.or(info.date_created),
+    }
+}
+
+/// Serialize logins as Bitwarden JSON
+pub fn format_bitwarden_json(
+    logins: &[LoginInfoWithProfile],
+    folders: bool,
+) -> Result<String, DecryptError> {
+    serde_json::to_string_pretty(&to_bitwarden(logins, folders))
+        .map_err(|e| DecryptError::FormatError(format!("Failed to serialize JSON: {}", e)))
+}
+
+/// Parse a Bitwarden JSON export into logins, with each login's folder name
+///
+/// Items other than logins are skipped; encrypted exports are rejected.
+pub fn parse_bitwarden_json(input: &str) -> Result<Vec<(Option<String>, LoginInfo)>, DecryptError> {
+    let export: BitwardenExport = serde_json::from_str(input)
+        .map_err(|e| DecryptError::FormatError(format!("Invalid Bitwarden JSON: {}", e)))?;
+    if export.encrypted {
+        return Err(DecryptError::FormatError(
+            "Encrypted Bitwarden exports are not supported".to_string(),
+        ));
+    }
+
+    let logins = export
+        .items
+        .iter()
+        .filter(|item| item.item_type == ITEM_TYPE_LOGIN)
+        .map(|item| {
+            let folder = item.folder_id.as_ref().and_then(|id| {
+                export
+                    .folders
+                    .iter()
+                    .find(|folder| &folder.id == id)
+                    .map(|folder| folder.name.clone())
+            });
+            let login = item.login.as_ref();
+            let url = login
+                .and_then(|login| login.uris.first())
+                .map(|uri| uri.uri.clone())
+                .unwrap_or_default();
+            let info = LoginInfo {
+                origin_url: url.clone(),
+                url,
+                username: login.and_then(|l| l.username.clone()).unwrap_or_default(),
+                password: login.and_then(|l| l.password.clone()).unwrap_or_default(),
+                note: item.notes.clone().unwrap_or_default(),
+                date_created: item.creation_date,
+                date_password_modified: item.revision_date,
+                ..Default::default()
+            };
+            (folder, info)
+        })
+        .collect();
+
+    Ok(logins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::Browser;
+    use crate::test_support::TestProfileLogin;
+    use chrono::TimeZone;
+
+    /// Abridged export written by the Bitwarden web vault
+    const SAMPLE_EXPORT: &str = r#"{
+      "encrypted": false,
+      "folders": [
+        { "id": "d8a8ec4c-6cb0-4d3f-9e2c-b0f3011a5e61", "name": "Work" }
+      ],
+      "items": [
+        {
+          "id": "0a2c3a36-4b8e-4f43-a8a5-b0f3011a6c0f",
+          "organizationId": null,
+          "folderId": "d8a8ec4c-6cb0-4d3f-9e2c-b0f3011a5e61",
+          "type": 1,
+          "reprompt": 0,
+          "name": "GitHub",
+          "notes": "recovery codes in the safe",
+          "favorite": true,
+          "login": {
+            "uris": [{ "match": null, "uri": "https://github.com/login" }],
+            "username": "alice",
+            "password": "hunter2",
+            "totp": null
+          },
+          "collectionIds": null,
+          "revisionDate": "2024-03-01T10:00:00.000Z",
+          "creationDate": "2023-01-15T08:30:00.000Z",
+          "deletedDate": null
+        },
+        {
+          "id": "5e0d4a1c-7d54-4f0e-9d0b-b0f3011a7a11",
+          "folderId": null,
+          "type": 2,
+          "name": "Wi-Fi",
+          "notes": "not a login",
+          "favorite": false,
+          "secureNote": { "type": 0 }
+        }
+      ]
+    }"#;
+
+    #[test]
+    fn test_parse_sample_export() {
+        let logins = parse_bitwarden_json(SAMPLE_EXPORT).unwrap();
+
+        assert_eq!(logins.len(), 1);
+        let (folder, info) = &logins[0];
+        assert_eq!(folder.as_deref(), Some("Work"));
+        assert_eq!(info.url, "https://github.com/login");
+        assert_eq!(info.username, "alice");
+        assert_eq!(info.password, "hunter2");
+        assert_eq!(info.note, "recovery codes in the safe");
+        assert_eq!(
+            info.date_created,
+            Some(Utc.with_ymd_and_hms(2023, 1, 15, 8, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_bitwarden_round_trip() {
+        let created = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let login = |url: &str| {
+            TestProfileLogin::new(url, "alice", "p\"ass\nword").date_created(Some(created))
+        };
+        let logins = vec![
+            login("https://www.example.com/").note("pin: 1234").build(),
+            login("https://b.example/").profile("Profile 1").build(),
+            login("https://c.example/").browser(Browser::Brave).build(),
+            login("https://d.example/").build(),
+        ];
+
+        let json = format_bitwarden_json(&logins, true).unwrap();
+        let export: BitwardenExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(export.folders.len(), 3);
+        assert_eq!(export.items[0].name, "example.com");
+        assert_eq!(export.items[1].notes, None);
+
+        let parsed = parse_bitwarden_json(&json).unwrap();
+        let folders: Vec<_> = parsed.iter().map(|(f, _)| f.as_deref()).collect();
+        assert_eq!(
+            folders,
+            [
+                Some("Default"),
+                Some("Profile 1"),
+                Some("Default (Brave)"),
+                Some("Default")
+            ]
+        );
+        let (_, first) = &parsed[0];
+        assert_eq!(first.url, "https://www.example.com/");
+        assert_eq!(first.password, "p\"ass\nword");
+        assert_eq!(first.note, "pin: 1234");
+        assert_eq!(first.date_created, Some(created));
+        assert_eq!(first.date_password_modified, Some(created));
+
+        // Without folders every item is unfiled
+        let flat = parse_bitwarden_json(&format_bitwarden_json(&logins, false).unwrap()).unwrap();
+        assert!(flat.iter().all(|(folder, _)| folder.is_none()));
+    }
+}
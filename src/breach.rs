@@ -0,0 +1,382 @@
+//! Offline breach check against a Have I Been Pwned "Pwned Passwords" dump
+//!
+//! Two dump formats are supported, both sorted by hash:
+//! - the SHA-1 text file as downloaded (`HASH:COUNT` lines)
+//! - a binary index built from it by [`build_index`]: an 8-byte magic
+//!   followed by fixed-size records (20-byte SHA-1, little-endian u32 count)
+//!
+//! Lookups binary-search the file on disk, so the multi-gigabyte dumps are
+//! never loaded into memory. Passwords are only hashed, never written.
+
+use crate::browser::Browser;
+use crate::models::{DecryptError, LoginInfoWithProfile};
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Magic bytes at the start of a binary index
+pub const INDEX_MAGIC: &[u8; 8] = b"CPUHIBP1";
+
+/// Size of one index record: SHA-1 hash plus u32 count
+const RECORD_LEN: u64 = 24;
+
+/// Below this many bytes the text search switches to a linear scan
+const TEXT_SCAN_THRESHOLD: u64 = 4096;
+
+/// Bytes read per probe, enough for a partial line plus a full line
+const TEXT_PROBE_LEN: usize = 256;
+
+type Sha1Hash = [u8; 20];
+
+/// Format of an opened dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DumpFormat {
+    Text,
+    Index,
+}
+
+/// An opened Pwned Passwords dump
+#[derive(Debug)]
+pub struct PwnedPasswords {
+    file: File,
+    len: u64,
+    format: DumpFormat,
+}
+
+impl PwnedPasswords {
+    /// Open a text dump or binary index, detected by the index magic
+    pub fn open(path: &Path) -> Result<Self, DecryptError> {
+        let file = File::open(path)
+            .map_err(|e| DecryptError::IoError(format!("Cannot open {}: {}", path.display(), e)))?;
+        let len = file.metadata()?.len();
+
+        let mut magic = [0u8; 8];
+        let format = if len >= magic.len() as u64
+            && file.read_exact_at(&mut magic, 0).is_ok()
+            && &magic == INDEX_MAGIC
+        {
+            if !(len - magic.len() as u64).is_multiple_of(RECORD_LEN) {
+                return Err(DecryptError::FormatError(format!(
+                    "Truncated breach index: {}",
+                    path.display()
+                )));
+            }
+            DumpFormat::Index
+        } else {
+            DumpFormat::Text
+        };
+        tracing::debug!("Opened {:?} breach dump {}", format, path.display());
+
+        Ok(Self { file, len, format })
+    }
+
+    /// How many times a password was seen in breaches, `None` if never
+    pub fn lookup(&self, password: &str) -> Result<Option<u32>, DecryptError> {
+        self.lookup_hash(&Sha1::digest(password.as_bytes()).into())
+    }
+
+    /// How many times a SHA-1 hash was seen in breaches, `None` if never
+    pub fn lookup_hash(&self, hash: &Sha1Hash) -> Result<Option<u32>, DecryptError> {
+        match self.format {
+            DumpFormat::Text => self.lookup_text(hash),
+            DumpFormat::Index => self.lookup_index(hash),
+        }
+    }
+
+    fn lookup_index(&self, hash: &Sha1Hash) -> Result<Option<u32>, DecryptError> {
+        let start = INDEX_MAGIC.len() as u64;
+        let (mut lo, mut hi) = (0, (self.len - start) / RECORD_LEN);
+        let mut record = [0u8; RECORD_LEN as usize];
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            self.file
+                .read_exact_at(&mut record, start + mid * RECORD_LEN)?;
+            match record[..20].cmp(hash) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => {
+                    let count = u32::from_le_bytes(record[20..].try_into().unwrap());
+                    return Ok(Some(count));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn lookup_text(&self, hash: &Sha1Hash) -> Result<Option<u32>, DecryptError> {
+        // `lo` is always the start of a line at or before the target's line
+        let (mut lo, mut hi) = (0u64, self.len);
+
+        while hi - lo > TEXT_SCAN_THRESHOLD {
+            let mid = lo + (hi - lo) / 2;
+            let chunk = self.read_chunk(mid, TEXT_PROBE_LEN)?;
+            // Skip the partial line `mid` points into
+            let Some(skip) = chunk.iter().position(|&b| b == b'\n') else {
+                hi = mid + 1;
+                continue;
+            };
+            let rest = &chunk[skip + 1..];
+            let Some(line_len) = rest.iter().position(|&b| b == b'\n') else {
+                // No complete line after `mid`: the target starts before it
+                hi = mid + 1;
+                continue;
+            };
+            let (line_hash, count) = parse_text_line(&rest[..line_len])?;
+            match line_hash.cmp(hash) {
+                Ordering::Less => lo = mid + (skip + 1 + line_len + 1) as u64,
+                Ordering::Greater => hi = mid + 1,
+                Ordering::Equal => return Ok(Some(count)),
+            }
+        }
+
+        // Linear scan of the remaining range (plus the line straddling `hi`)
+        let chunk = self.read_chunk(lo, (hi - lo) as usize + TEXT_PROBE_LEN)?;
+        for line in chunk.split(|&b| b == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let Ok((line_hash, count)) = parse_text_line(line) else {
+                // Truncated last line of the chunk
+                break;
+            };
+            match line_hash.cmp(hash) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Ok(Some(count)),
+                Ordering::Greater => break,
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_chunk(&self, offset: u64, len: usize) -> Result<Vec<u8>, DecryptError> {
+        let len = len.min(self.len.saturating_sub(offset) as usize);
+        let mut buf = vec![0u8; len];
+        self.file.read_exact_at(&mut buf, offset)?;
+        Ok(buf)
+    }
+}
+
+/// Parse a `HASH:COUNT` line (trailing `\r` allowed)
+fn parse_text_line(line: &[u8]) -> Result<(Sha1Hash, u32), DecryptError> {
+    let invalid = || {
+        DecryptError::FormatError(format!(
+            "Invalid Pwned Passwords line: {:?}",
+            String::from_utf8_lossy(&line[..line.len().min(64)])
+        ))
+    };
+    let line = std::str::from_utf8(line).map_err(|_| invalid())?.trim();
+    let (hash, count) = line.split_once(':').ok_or_else(invalid)?;
+
+    let mut parsed = [0u8; 20];
+    hex::decode_to_slice(hash, &mut parsed).map_err(|_| invalid())?;
+    // Counts above u32::MAX do not exist in practice; saturate rather than fail
+    let count = count
+        .parse::<u64>()
+        .map_err(|_| invalid())?
+        .min(u64::from(u32::MAX)) as u32;
+    Ok((parsed, count))
+}
+
+/// Build a binary index from a text dump sorted by hash
+///
+/// Returns the number of hashes written. The index is written to a
+/// temporary file and renamed into place once complete.
+pub fn build_index(text_path: &Path, index_path: &Path) -> Result<u64, DecryptError> {
+    let input = File::open(text_path).map_err(|e| {
+        DecryptError::IoError(format!("Cannot open {}: {}", text_path.display(), e))
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(
+        index_path.file_name().ok_or_else(|| {
+            DecryptError::IoError(format!("Invalid path: {}", index_path.display()))
+        })?,
+    );
+    temp_name.push(format!(".tmp{}", std::process::id()));
+    let temp_path = index_path.with_file_name(temp_name);
+
+    let result = write_index(BufReader::new(input), &temp_path);
+    match result {
+        Ok(count) => {
+            std::fs::rename(&temp_path, index_path)?;
+            Ok(count)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+fn write_index(input: impl BufRead, path: &Path) -> Result<u64, DecryptError> {
+    let mut output = BufWriter::new(File::create(path)?);
+    output.write_all(INDEX_MAGIC)?;
+
+    let mut previous: Option<Sha1Hash> = None;
+    let mut count = 0u64;
+    for (number, line) in input.split(b'\n').enumerate() {
+        let line = line?;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let (hash, seen) = parse_text_line(&line)?;
+        if previous.is_some_and(|previous| previous >= hash) {
+            return Err(DecryptError::FormatError(format!(
+                "Pwned Passwords file is not sorted by hash (line {})",
+                number + 1
+            )));
+        }
+        output.write_all(&hash)?;
+        output.write_all(&seen.to_le_bytes())?;
+        previous = Some(hash);
+        count += 1;
+    }
+
+    output.flush()?;
+    output.get_ref().sync_all()?;
+    Ok(count)
+}
+
+/// A saved login whose password appears in the dump
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BreachFinding {
+    pub browser: Browser,
+    pub profile: String,
+    pub url: String,
+    pub username: String,
+    /// Number of times the password was seen in breaches
+    pub count: u32,
+}
+
+/// Result of a breach check
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BreachReport {
+    /// Number of logins checked
+    pub checked: usize,
+    pub findings: Vec<BreachFinding>,
+}
+
+/// Check decrypted logins against the dump
+///
+/// Each distinct password is looked up once.
+pub fn check_logins(
+    logins: &[LoginInfoWithProfile],
+    dump: &PwnedPasswords,
+) -> Result<BreachReport, DecryptError> {
+    let mut seen: HashMap<Sha1Hash, Option<u32>> = HashMap::new();
+    let mut findings = Vec::new();
+
+    for login in logins {
+        let hash: Sha1Hash = Sha1::digest(login.info.password.as_bytes()).into();
+        let count = match seen.get(&hash) {
+            Some(count) => *count,
+            None => {
+                let count = dump.lookup_hash(&hash)?;
+                seen.insert(hash, count);
+                count
+            }
+        };
+        if let Some(count) = count {
+            findings.push(BreachFinding {
+                browser: login.browser,
+                profile: login.profile.clone(),
+                url: login.info.url.clone(),
+                username: login.info.username.clone(),
+                count,
+            });
+        }
+    }
+
+    Ok(BreachReport {
+        checked: logins.len(),
+        findings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestProfileLogin;
+
+    /// A sorted dump with the given passwords and a few thousand filler hashes
+    fn write_dump(path: &Path, passwords: &[(&str, u32)]) {
+        let mut lines: Vec<String> = (0..5000)
+            .map(|i| format!("filler-{}", i))
+            .chain(passwords.iter().map(|(p, _)| p.to_string()))
+            .map(|password| {
+                let count = passwords
+                    .iter()
+                    .find(|(p, _)| *p == password)
+                    .map_or(1, |(_, c)| *c);
+                format!(
+                    "{}:{}",
+                    hex::encode_upper(Sha1::digest(password.as_bytes())),
+                    count
+                )
+            })
+            .collect();
+        lines.sort();
+        std::fs::write(path, lines.join("\r\n") + "\r\n").unwrap();
+    }
+
+    #[test]
+    fn test_lookup_text_and_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("pwned.txt");
+        let index = dir.path().join("pwned.idx");
+        write_dump(&text, &[("password", 9_545_824), ("hunter2", 17_043)]);
+
+        assert_eq!(build_index(&text, &index).unwrap(), 5002);
+        for path in [&text, &index] {
+            let dump = PwnedPasswords::open(path).unwrap();
+            assert_eq!(dump.lookup("password").unwrap(), Some(9_545_824));
+            assert_eq!(dump.lookup("hunter2").unwrap(), Some(17_043));
+            assert_eq!(dump.lookup("filler-0").unwrap(), Some(1));
+            assert_eq!(dump.lookup("filler-4999").unwrap(), Some(1));
+            assert_eq!(dump.lookup("not in the dump").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_build_index_rejects_unsorted_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("pwned.txt");
+        let index = dir.path().join("pwned.idx");
+        std::fs::write(
+            &text,
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:1\n\
+             0000000000000000000000000000000000000000:1\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            build_index(&text, &index),
+            Err(DecryptError::FormatError(_))
+        ));
+        assert!(!index.exists());
+    }
+
+    #[test]
+    fn test_check_logins() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("pwned.txt");
+        write_dump(&text, &[("password", 42)]);
+        let dump = PwnedPasswords::open(&text).unwrap();
+
+        let logins = vec![
+            TestProfileLogin::new("https://a.example/", "alice", "password").build(),
+            TestProfileLogin::new("https://b.example/", "alice", "x8#Kq!2vLp9@Wz").build(),
+            TestProfileLogin::new("https://c.example/", "alice", "password").build(),
+        ];
+
+        let report = check_logins(&logins, &dump).unwrap();
+        assert_eq!(report.checked, 3);
+        let urls: Vec<_> = report.findings.iter().map(|f| f.url.as_str()).collect();
+        assert_eq!(urls, ["https://a.example/", "https://c.example/"]);
+        assert!(report.findings.iter().all(|f| f.count == 42));
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Chromium-family browsers sharing Chrome's password storage format
+///
+/// Each browser has its own user data directory and its own "Safe Storage"
+/// secret in the platform key store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Browser {
+    #[default]
+    Chrome,
+    ChromeBeta,
+    ChromeDev,
+    ChromeCanary,
+    Chromium,
+    Brave,
+    Edge,
+    Vivaldi,
+    Opera,
+}
+
+impl Browser {
+    /// Every supported browser
+    pub const ALL: [Browser; 9] = [
+        Browser::Chrome,
+        Browser::ChromeBeta,
+        Browser::ChromeDev,
+        Browser::ChromeCanary,
+        Browser::Chromium,
+        Browser::Brave,
+        Browser::Edge,
+        Browser::Vivaldi,
+        Browser::Opera,
+    ];
+
+    /// Human-readable name
+    pub fn name(self) -> &'static str {
+        match self {
+            Browser::Chrome => "Google Chrome",
+            Browser::ChromeBeta => "Google Chrome Beta",
+            Browser::ChromeDev => "Google Chrome Dev",
+            Browser::ChromeCanary => "Google Chrome Canary",
+            Browser::Chromium => "Chromium",
+            Browser::Brave => "Brave",
+            Browser::Edge => "Microsoft Edge",
+            Browser::Vivaldi => "Vivaldi",
+            Browser::Opera => "Opera",
+        }
+    }
+
+    /// Identifier used on the command line and in file names
+    pub fn slug(self) -> &'static str {
+        match self {
+            Browser::Chrome => "chrome",
+            Browser::ChromeBeta => "chrome-beta",
+            Browser::ChromeDev => "chrome-dev",
+            Browser::ChromeCanary => "chrome-canary",
+            Browser::Chromium => "chromium",
+            Browser::Brave => "brave",
+            Browser::Edge => "edge",
+            Browser::Vivaldi => "vivaldi",
+            Browser::Opera => "opera",
+        }
+    }
+
+    /// Product name used in the key store entries ("<product> Safe Storage")
+    fn product(self) -> &'static str {
+        match self {
+            Browser::Chrome | Browser::ChromeBeta | Browser::ChromeDev | Browser::ChromeCanary => {
+                "Chrome"
+            }
+            Browser::Chromium => "Chromium",
+            Browser::Brave => "Brave",
+            Browser::Edge => "Microsoft Edge",
+            Browser::Vivaldi => "Vivaldi",
+            Browser::Opera => "Opera",
+        }
+    }
+
+    /// Key store service name / label, e.g. "Chrome Safe Storage"
+    pub fn safe_storage_service(self) -> String {
+        format!("{} Safe Storage", self.product())
+    }
+
+    /// macOS Keychain account name, e.g. "Chrome"
+    pub fn safe_storage_account(self) -> &'static str {
+        self.product()
+    }
+
+    /// KWallet folder holding the secret, e.g. "Chrome Keys"
+    pub fn kwallet_folder(self) -> String {
+        format!("{} Keys", self.product())
+    }
+
+    /// `application` attribute of the Secret Service item
+    pub fn secret_service_application(self) -> &'static str {
+        match self {
+            Browser::Chrome | Browser::ChromeBeta | Browser::ChromeDev | Browser::ChromeCanary => {
+                "chrome"
+            }
+            Browser::Chromium => "chromium",
+            Browser::Brave => "brave",
+            Browser::Edge => "microsoft-edge",
+            Browser::Vivaldi => "vivaldi",
+            Browser::Opera => "opera",
+        }
+    }
+
+    /// User data directory, relative to the platform's application data dir
+    #[cfg(target_os = "macos")]
+    fn relative_data_dir(self) -> Option<&'static str> {
+        Some(match self {
+            Browser::Chrome => "Google/Chrome",
+            Browser::ChromeBeta => "Google/Chrome Beta",
+            Browser::ChromeDev => "Google/Chrome Dev",
+            Browser::ChromeCanary => "Google/Chrome Canary",
+            Browser::Chromium => "Chromium",
+            Browser::Brave => "BraveSoftware/Brave-Browser",
+            Browser::Edge => "Microsoft Edge",
+            Browser::Vivaldi => "Vivaldi",
+            Browser::Opera => "com.operasoftware.Opera",
+        })
+    }
+
+    /// User data directory, relative to the platform's application data dir
+    #[cfg(not(target_os = "macos"))]
+    fn relative_data_dir(self) -> Option<&'static str> {
+        match self {
+            Browser::Chrome => Some("google-chrome"),
+            Browser::ChromeBeta => Some("google-chrome-beta"),
+            Browser::ChromeDev => Some("google-chrome-unstable"),
+            // Canary is not released for Linux
+            Browser::ChromeCanary => None,
+            Browser::Chromium => Some("chromium"),
+            Browser::Brave => Some("BraveSoftware/Brave-Browser"),
+            Browser::Edge => Some("microsoft-edge"),
+            Browser::Vivaldi => Some("vivaldi"),
+            Browser::Opera => Some("opera"),
+        }
+    }
+
+    /// Default user data directory of this browser
+    pub fn data_dir(self) -> Option<PathBuf> {
+        let relative = self.relative_data_dir()?;
+
+        #[cfg(target_os = "macos")]
+        {
+            dirs::home_dir().map(|home| home.join("Library/Application Support").join(relative))
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        {
+            // Linux: ~/.config/<browser>
+            dirs::config_dir().map(|dir| dir.join(relative))
+        }
+    }
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Browsers selected on the command line: one browser or "all"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowserSelection {
+    All,
+    Only(Browser),
+}
+
+impl Default for BrowserSelection {
+    fn default() -> Self {
+        BrowserSelection::Only(Browser::Chrome)
+    }
+}
+
+impl BrowserSelection {
+    /// The selected browsers
+    pub fn browsers(self) -> Vec<Browser> {
+        match self {
+            BrowserSelection::All => Browser::ALL.to_vec(),
+            BrowserSelection::Only(browser) => vec![browser],
+        }
+    }
+}
+
+impl FromStr for BrowserSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(BrowserSelection::All);
+        }
+        <Browser as clap::ValueEnum>::from_str(s, true)
+            .map(BrowserSelection::Only)
+            .map_err(|_| {
+                let names: Vec<&str> = Browser::ALL.iter().map(|b| b.slug()).collect();
+                format!(
+                    "unknown browser '{}' (expected all, {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_store_names() {
+        assert_eq!(
+            Browser::Chrome.safe_storage_service(),
+            "Chrome Safe Storage"
+        );
+        assert_eq!(Browser::Chrome.safe_storage_account(), "Chrome");
+        assert_eq!(Browser::Brave.safe_storage_service(), "Brave Safe Storage");
+        assert_eq!(
+            Browser::Edge.safe_storage_service(),
+            "Microsoft Edge Safe Storage"
+        );
+        assert_eq!(Browser::Chromium.kwallet_folder(), "Chromium Keys");
+    }
+
+    #[test]
+    fn test_parse_browser_selection() {
+        assert_eq!("all".parse(), Ok(BrowserSelection::All));
+        assert_eq!("brave".parse(), Ok(BrowserSelection::Only(Browser::Brave)));
+        assert_eq!(
+            "chrome-canary".parse(),
+            Ok(BrowserSelection::Only(Browser::ChromeCanary))
+        );
+        assert!("netscape".parse::<BrowserSelection>().is_err());
+        assert_eq!(BrowserSelection::All.browsers().len(), Browser::ALL.len());
+    }
+}
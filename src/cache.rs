@@ -0,0 +1,398 @@
+use crate::browser::Browser;
+use crate::models::DecryptError;
+use crate::output::write_private_file;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::fs::DirBuilder;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Cache directory name under the home directory
+const CACHE_DIR_NAME: &str = ".chrome-password-unlock";
+
+/// Encrypted cache file name (Chrome; other browsers add their name)
+const CACHE_FILE_NAME: &str = "master_password.enc";
+
+/// Plaintext cache file written by older versions
+const LEGACY_CACHE_FILE_NAME: &str = "master_password";
+
+/// Cache file format version
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// PBKDF2-HMAC-SHA256 iterations for the passphrase
+#[cfg(not(test))]
+const PASSPHRASE_ITERATIONS: u32 = 600_000;
+#[cfg(test)]
+const PASSPHRASE_ITERATIONS: u32 = 1_000;
+
+const SALT_LENGTH: usize = 16;
+
+/// Default time-to-live of a cached secret
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// On-disk representation of the encrypted cache
+///
+/// `created_at`/`expires_at` are stored in the clear so `cache status` works
+/// without the passphrase; they are bound to the ciphertext as associated data.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CacheFile {
+    version: u32,
+    created_at: u64,
+    expires_at: u64,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl CacheFile {
+    fn associated_data(&self) -> String {
+        format!("{}:{}:{}", self.version, self.created_at, self.expires_at)
+    }
+}
+
+/// State of the cache directory, as reported by `cache status`
+#[derive(Debug, Clone)]
+pub struct CacheStatus {
+    pub path: PathBuf,
+    /// Creation and expiry time of the encrypted cache, if present
+    pub entry: Option<(SystemTime, SystemTime)>,
+    /// Permission bits of the encrypted cache file
+    pub mode: Option<u32>,
+    /// A plaintext cache from an older version is still on disk
+    pub legacy_plaintext: bool,
+}
+
+impl CacheStatus {
+    /// Whether a non-expired encrypted entry exists
+    pub fn is_valid(&self) -> bool {
+        self.entry
+            .is_some_and(|(_, expires_at)| expires_at > SystemTime::now())
+    }
+}
+
+/// Opt-in, passphrase-encrypted cache of the Safe Storage secret
+///
+/// The secret is encrypted with AES-256-GCM under a key derived from the
+/// passphrase, stored with 0600 permissions in a 0700 directory and
+/// discarded once its TTL has passed.
+pub struct MasterPasswordCache {
+    dir: PathBuf,
+    browser: Browser,
+    passphrase: String,
+    ttl: Duration,
+}
+
+impl MasterPasswordCache {
+    /// Cache in the default directory (`~/.chrome-password-unlock`)
+    pub fn new(passphrase: String, ttl: Duration) -> Result<Self, DecryptError> {
+        Ok(Self::with_dir(default_cache_dir()?, passphrase, ttl))
+    }
+
+    /// Cache in a custom directory
+    pub fn with_dir(dir: PathBuf, passphrase: String, ttl: Duration) -> Self {
+        Self {
+            dir,
+            browser: Browser::default(),
+            passphrase,
+            ttl,
+        }
+    }
+
+    /// Cache the secret of another browser (each has its own entry)
+    pub fn for_browser(mut self, browser: Browser) -> Self {
+        self.browser = browser;
+        self
+    }
+
+    fn file_path(&self) -> PathBuf {
+        cache_file_path(&self.dir, self.browser)
+    }
+
+    /// Load the cached secret
+    ///
+    /// Returns `Ok(None)` if there is no entry or it has expired (expired
+    /// entries are deleted). A wrong passphrase is an error.
+    pub fn load(&self) -> Result<Option<String>, DecryptError> {
+        let path = self.file_path();
+        if !path.exists() {
+            tracing::debug!("No cached master password found");
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| DecryptError::IoError(format!("Failed to read cache: {}", e)))?;
+        let file: CacheFile = serde_json::from_str(&content)
+            .map_err(|e| DecryptError::IoError(format!("Corrupt cache file: {}", e)))?;
+
+        if file.version != CACHE_FORMAT_VERSION || file.expires_at <= unix_now() {
+            tracing::debug!("Cached master password expired, removing it");
+            self.invalidate()?;
+            return Ok(None);
+        }
+
+        let salt = decode_hex(&file.salt)?;
+        let nonce = decode_hex(&file.nonce)?;
+        let ciphertext = decode_hex(&file.ciphertext)?;
+        if nonce.len() != 12 {
+            return Err(DecryptError::CryptoError("Corrupt cache nonce".to_string()));
+        }
+
+        let cipher = self.cipher(&salt);
+        let aad = file.associated_data();
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                DecryptError::CryptoError("Failed to decrypt cache (wrong passphrase?)".to_string())
+            })?;
+
+        tracing::debug!("Loaded master password from encrypted cache");
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|e| DecryptError::CryptoError(format!("Invalid UTF-8 in cache: {}", e)))
+    }
+
+    /// Encrypt and store the secret
+    pub fn store(&self, secret: &str) -> Result<(), DecryptError> {
+        create_private_dir(&self.dir)?;
+
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let created_at = unix_now();
+
+        let mut file = CacheFile {
+            version: CACHE_FORMAT_VERSION,
+            created_at,
+            expires_at: created_at.saturating_add(self.ttl.as_secs()),
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: String::new(),
+        };
+
+        let aad = file.associated_data();
+        let ciphertext = self
+            .cipher(&salt)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: secret.as_bytes(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|e| DecryptError::CryptoError(format!("Failed to encrypt cache: {}", e)))?;
+        file.ciphertext = hex::encode(ciphertext);
+
+        let content = serde_json::to_string(&file)
+            .map_err(|e| DecryptError::IoError(format!("Failed to serialize cache: {}", e)))?;
+        write_private_file(&self.file_path(), content.as_bytes())?;
+        tracing::debug!("Cached master password (encrypted, ttl {:?})", self.ttl);
+
+        Ok(())
+    }
+
+    /// Remove the cached secret
+    pub fn invalidate(&self) -> Result<(), DecryptError> {
+        remove_if_exists(&self.file_path())
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Aes256Gcm {
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+            self.passphrase.as_bytes(),
+            salt,
+            PASSPHRASE_ITERATIONS,
+            &mut key,
+        );
+        Aes256Gcm::new(&key.into())
+    }
+}
+
+/// Default cache directory (`~/.chrome-password-unlock`)
+pub fn default_cache_dir() -> Result<PathBuf, DecryptError> {
+    Ok(dirs::home_dir()
+        .ok_or_else(|| DecryptError::IoError("Cannot find home directory".to_string()))?
+        .join(CACHE_DIR_NAME))
+}
+
+/// Path of a browser's encrypted cache file in `dir`
+fn cache_file_path(dir: &Path, browser: Browser) -> PathBuf {
+    match browser {
+        Browser::Chrome => dir.join(CACHE_FILE_NAME),
+        other => dir.join(format!("master_password-{}.enc", other.slug())),
+    }
+}
+
+/// Remove every cached secret in `dir`, including legacy plaintext caches
+pub fn clear_cache(dir: &Path) -> Result<(), DecryptError> {
+    for browser in Browser::ALL {
+        remove_if_exists(&cache_file_path(dir, browser))?;
+    }
+    remove_if_exists(&dir.join(LEGACY_CACHE_FILE_NAME))?;
+    tracing::debug!("Cleared master password cache in {}", dir.display());
+    Ok(())
+}
+
+/// Inspect a browser's cache entry without decrypting anything
+pub fn cache_status(dir: &Path, browser: Browser) -> Result<CacheStatus, DecryptError> {
+    let path = cache_file_path(dir, browser);
+    let legacy_plaintext = dir.join(LEGACY_CACHE_FILE_NAME).exists();
+
+    if !path.exists() {
+        return Ok(CacheStatus {
+            path,
+            entry: None,
+            mode: None,
+            legacy_plaintext,
+        });
+    }
+
+    let mode = std::fs::metadata(&path)?.permissions().mode() & 0o777;
+    let content = std::fs::read_to_string(&path)?;
+    let file: CacheFile = serde_json::from_str(&content)
+        .map_err(|e| DecryptError::IoError(format!("Corrupt cache file: {}", e)))?;
+    let entry = Some((
+        UNIX_EPOCH + Duration::from_secs(file.created_at),
+        UNIX_EPOCH + Duration::from_secs(file.expires_at),
+    ));
+
+    Ok(CacheStatus {
+        path,
+        entry,
+        mode: Some(mode),
+        legacy_plaintext,
+    })
+}
+
+/// Whether a plaintext cache from an older version exists in `dir`
+pub fn has_legacy_plaintext_cache(dir: &Path) -> bool {
+    dir.join(LEGACY_CACHE_FILE_NAME).exists()
+}
+
+/// Parse a TTL such as "90", "30m", "8h" or "2d" (bare numbers are seconds)
+pub fn parse_ttl(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid duration unit in '{}' (use s, m, h or d)",
+                s
+            ));
+        }
+    };
+    Ok(Duration::from_secs(value.saturating_mul(multiplier)))
+}
+
+fn create_private_dir(dir: &Path) -> Result<(), DecryptError> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    // The directory may predate this version with looser permissions
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+        .map_err(|e| DecryptError::IoError(format!("Failed to set permissions: {}", e)))
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), DecryptError> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, DecryptError> {
+    hex::decode(s).map_err(|e| DecryptError::IoError(format!("Corrupt cache file: {}", e)))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip_with_private_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let cache = MasterPasswordCache::with_dir(cache_dir.clone(), "pass".into(), DEFAULT_TTL);
+
+        assert_eq!(cache.load().unwrap(), None);
+        cache.store("safe-storage-secret").unwrap();
+        assert_eq!(
+            cache.load().unwrap().as_deref(),
+            Some("safe-storage-secret")
+        );
+
+        let raw = std::fs::read_to_string(cache_dir.join(CACHE_FILE_NAME)).unwrap();
+        assert!(!raw.contains("safe-storage-secret"));
+
+        let dir_mode = std::fs::metadata(&cache_dir).unwrap().permissions().mode();
+        assert_eq!(dir_mode & 0o777, 0o700);
+        let status = cache_status(&cache_dir, Browser::Chrome).unwrap();
+        assert_eq!(status.mode, Some(0o600));
+        assert!(status.is_valid());
+    }
+
+    #[test]
+    fn test_cache_wrong_passphrase_and_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MasterPasswordCache::with_dir(dir.path().into(), "right".into(), DEFAULT_TTL);
+        cache.store("secret").unwrap();
+
+        let wrong = MasterPasswordCache::with_dir(dir.path().into(), "wrong".into(), DEFAULT_TTL);
+        assert!(wrong.load().is_err());
+
+        let expired =
+            MasterPasswordCache::with_dir(dir.path().into(), "right".into(), Duration::ZERO);
+        expired.store("secret").unwrap();
+        assert_eq!(expired.load().unwrap(), None);
+        assert!(!dir.path().join(CACHE_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_cache_entries_are_per_browser() {
+        let dir = tempfile::tempdir().unwrap();
+        let chrome = MasterPasswordCache::with_dir(dir.path().into(), "pass".into(), DEFAULT_TTL);
+        let brave = MasterPasswordCache::with_dir(dir.path().into(), "pass".into(), DEFAULT_TTL)
+            .for_browser(Browser::Brave);
+        chrome.store("chrome-secret").unwrap();
+        brave.store("brave-secret").unwrap();
+
+        assert_eq!(chrome.load().unwrap().as_deref(), Some("chrome-secret"));
+        assert_eq!(brave.load().unwrap().as_deref(), Some("brave-secret"));
+
+        clear_cache(dir.path()).unwrap();
+        assert!(!cache_status(dir.path(), Browser::Brave).unwrap().is_valid());
+        assert_eq!(chrome.load().unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_ttl("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_ttl("8h"), Ok(Duration::from_secs(28800)));
+        assert!(parse_ttl("8x").is_err());
+        assert!(parse_ttl("h").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Chrome / Google Password Manager CSV format
+//!
+//! Columns: `name,url,username,password,note`
+
+use crate::csv::{parse_records, write_record};
+use crate::models::{DecryptError, LoginInfo};
+
+/// Header written by Chrome's password exporter
+pub const CHROME_CSV_HEADER: [&str; 5] = ["name", "url", "username", "password", "note"];
+
+/// Format logins as a Chrome password CSV file
+pub fn format_chrome_csv(logins: &[LoginInfo]) -> String {
+    let mut output = String::new();
+    write_record(&mut output, &CHROME_CSV_HEADER);
+
+    for login in logins {
+        write_record(
+            &mut output,
+            &[
+                display_name(&login.url).as_str(),
+                login.url.as_str(),
+                login.username.as_str(),
+                login.password.as_str(),
+                login.note.as_str(),
+            ],
+        );
+    }
+
+    output
+}
+
+/// Parse a Chrome password CSV file
+///
+/// Columns are matched by header name, so files from other password managers
+/// using the same names (or common aliases such as `website`) work as well.
+pub fn parse_chrome_csv(input: &str) -> Result<Vec<LoginInfo>, DecryptError> {
+    let mut records = parse_records(input)?.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| DecryptError::FormatError("Empty CSV file".to_string()))?;
+
+    let column = |aliases: &[&str]| {
+        header
+            .iter()
+            .position(|name| aliases.contains(&name.trim().to_ascii_lowercase().as_str()))
+    };
+    let url_col = column(&["url", "website", "origin", "login_uri"])
+        .ok_or_else(|| DecryptError::FormatError("CSV has no 'url' column".to_string()))?;
+    let password_col = column(&["password"])
+        .ok_or_else(|| DecryptError::FormatError("CSV has no 'password' column".to_string()))?;
+    let username_col = column(&["username", "login", "login_username"]);
+    let note_col = column(&["note", "notes", "extra"]);
+
+    let field = |record: &[String], col: Option<usize>| {
+        col.and_then(|i| record.get(i)).cloned().unwrap_or_default()
+    };
+
+    let logins = records
+        .map(|record| {
+            let url = field(&record, Some(url_col));
+            LoginInfo {
+                origin_url: url.clone(),
+                url,
+                username: field(&record, username_col),
+                password: field(&record, Some(password_col)),
+                note: field(&record, note_col),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Ok(logins)
+}
+
+/// Name shown by Chrome for a login: the host without a leading "www."
+pub(crate) fn display_name(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => match parsed.host_str() {
+            Some(host) => host.strip_prefix("www.").unwrap_or(host).to_string(),
+            None => url.to_string(),
+        },
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login(url: &str, username: &str, password: &str) -> LoginInfo {
+        LoginInfo {
+            url: url.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_chrome_csv() {
+        let csv = format_chrome_csv(&[
+            login("https://www.example.com/login", "alice", "plain"),
+            login("https://github.com/session", "bob", "a,\"b\"\nc"),
+        ]);
+
+        assert_eq!(
+            csv,
+            "name,url,username,password,note\n\
+             example.com,https://www.example.com/login,alice,plain,\n\
+             github.com,https://github.com/session,bob,\"a,\"\"b\"\"\nc\",\n"
+        );
+    }
+
+    #[test]
+    fn test_chrome_csv_round_trip() {
+        let logins = vec![
+            LoginInfo {
+                note: "recovery codes:\n1234-5678".to_string(),
+                ..login("https://example.com/", "alice", "p,a\"ss\r\nword")
+            },
+            login("android://hash@com.example.app/", "", "x"),
+        ];
+        let parsed = parse_chrome_csv(&format_chrome_csv(&logins)).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].password, "p,a\"ss\r\nword");
+        assert_eq!(parsed[0].note, "recovery codes:\n1234-5678");
+        assert_eq!(parsed[1].url, "android://hash@com.example.app/");
+        assert_eq!(parsed[1].username, "");
+    }
+
+    #[test]
+    fn test_parse_reordered_columns() {
+        let parsed =
+            parse_chrome_csv("password,Username,URL\nsecret,carol,https://a.test\n").unwrap();
+        assert_eq!(parsed[0].url, "https://a.test");
+        assert_eq!(parsed[0].username, "carol");
+        assert_eq!(parsed[0].password, "secret");
+    }
+}
@@ -1,35 +1,123 @@
 use crate::models::DecryptError;
 use aes::Aes128;
 use cbc::cipher::block_padding::Pkcs7;
-use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 
 type Aes128CbcDec = cbc::Decryptor<Aes128>;
+type Aes128CbcEnc = cbc::Encryptor<Aes128>;
 
 /// Chrome encryption configuration constants
 pub const SALT: &[u8] = b"saltysalt";
-pub const ITERATIONS: u32 = 1003;
 pub const KEY_LENGTH: usize = 16;
 pub const IV: &[u8; 16] = b"                "; // 16 spaces
 
-/// Derive key using PBKDF2
+/// PBKDF2 iterations used by Chrome on macOS
+pub const MACOS_ITERATIONS: u32 = 1003;
+
+/// PBKDF2 iterations used by Chrome on Linux
+pub const LINUX_ITERATIONS: u32 = 1;
+
+/// Hard-coded password Chrome on Linux uses for "v10" blobs
+pub const LINUX_V10_PASSWORD: &str = "peanuts";
+
+/// PBKDF2 iterations for the current platform
+#[cfg(target_os = "macos")]
+pub const ITERATIONS: u32 = MACOS_ITERATIONS;
+
+/// PBKDF2 iterations for the current platform
+#[cfg(not(target_os = "macos"))]
+pub const ITERATIONS: u32 = LINUX_ITERATIONS;
+
+/// Length of the version prefix ("v10", "v11") in front of encrypted blobs
+const VERSION_PREFIX_LEN: usize = 3;
+
+/// Derive key using PBKDF2 with the current platform's iteration count
 pub fn derive_key(master_password: &str) -> Vec<u8> {
+    derive_key_with_iterations(master_password, ITERATIONS)
+}
+
+/// Derive key using PBKDF2 with an explicit iteration count
+pub fn derive_key_with_iterations(master_password: &str, iterations: u32) -> Vec<u8> {
     let mut key = vec![0u8; KEY_LENGTH];
-    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(master_password.as_bytes(), SALT, ITERATIONS, &mut key);
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(master_password.as_bytes(), SALT, iterations, &mut key);
     key
 }
 
+/// AES keys for each encryption version Chrome may have used
+///
+/// - macOS: every blob is "v10", keyed by the Keychain "Chrome Safe Storage" secret
+/// - Linux: "v10" is keyed by the hard-coded "peanuts" password, "v11" by the
+///   Secret Service "Chrome Safe Storage" secret (when available)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedKeys {
+    pub v10: Vec<u8>,
+    pub v11: Option<Vec<u8>>,
+}
+
+impl DerivedKeys {
+    /// Keys for a macOS profile, derived from the Keychain secret
+    pub fn for_macos(master_password: &str) -> Self {
+        Self {
+            v10: derive_key_with_iterations(master_password, MACOS_ITERATIONS),
+            v11: None,
+        }
+    }
+
+    /// Keys for a Linux profile, optionally derived from the Secret Service secret
+    pub fn for_linux(secret: Option<&str>) -> Self {
+        Self {
+            v10: derive_key_with_iterations(LINUX_V10_PASSWORD, LINUX_ITERATIONS),
+            v11: secret.map(|s| derive_key_with_iterations(s, LINUX_ITERATIONS)),
+        }
+    }
+
+    /// Version prefix Chrome writes new blobs with: "v11" when a Secret
+    /// Service key is available, "v10" otherwise
+    pub fn current_version(&self) -> &'static str {
+        if self.v11.is_some() { "v11" } else { "v10" }
+    }
+
+    /// Get the key for a version prefix such as "v10"
+    pub fn key_for_version(&self, version: &str) -> Option<&[u8]> {
+        match version {
+            "v10" => Some(&self.v10),
+            "v11" => self.v11.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Get the version prefix ("v10", "v11", ...) of an encrypted blob
+pub fn version_prefix(encrypted_password: &[u8]) -> Option<&str> {
+    let prefix = encrypted_password.get(..VERSION_PREFIX_LEN)?;
+    match prefix {
+        [b'v', a, b] if a.is_ascii_digit() && b.is_ascii_digit() => {
+            std::str::from_utf8(prefix).ok()
+        }
+        _ => None,
+    }
+}
+
 /// Decrypt Chrome password
 ///
 /// # Arguments
-/// - `derived_key`: Derived AES key
-/// - `encrypted_password`: Encrypted password data (Chrome removes the 3-byte "v10" prefix)
+/// - `keys`: Derived AES keys, selected by the blob's version prefix
+/// - `encrypted_password`: Encrypted password data, including the "v10"/"v11" prefix
 ///
 /// # Returns
 /// Decrypted plaintext password
 pub fn decrypt_password(
-    derived_key: &[u8],
+    keys: &DerivedKeys,
     encrypted_password: &[u8],
 ) -> Result<String, DecryptError> {
+    let version = version_prefix(encrypted_password).ok_or_else(|| {
+        DecryptError::CryptoError("Missing encryption version prefix".to_string())
+    })?;
+
+    let derived_key = keys.key_for_version(version).ok_or_else(|| {
+        DecryptError::CryptoError(format!("No key available for version {}", version))
+    })?;
+
     if derived_key.len() != KEY_LENGTH {
         return Err(DecryptError::CryptoError(format!(
             "Invalid key length: expected {}, got {}",
@@ -38,12 +126,7 @@ pub fn decrypt_password(
         )));
     }
 
-    // First 3 bytes of Chrome password are "v10" version identifier, need to skip
-    let encrypted_data = if encrypted_password.len() > 3 {
-        &encrypted_password[3..]
-    } else {
-        encrypted_password
-    };
+    let encrypted_data = &encrypted_password[VERSION_PREFIX_LEN..];
 
     // Decrypt using AES-128-CBC
     let decryptor = Aes128CbcDec::new(derived_key.into(), IV.into());
@@ -63,13 +146,38 @@ pub fn decrypt_password(
     let result = String::from_utf8(buffer)
         .map_err(|e| DecryptError::CryptoError(format!("Invalid UTF-8: {}", e)))?;
 
-    tracing::debug!("Successfully decrypted password");
+    tracing::debug!("Successfully decrypted {} password", version);
     Ok(result)
 }
 
+/// Encrypt a password the way Chrome does for new logins
+///
+/// The blob uses [`DerivedKeys::current_version`] and can be read back with
+/// [`decrypt_password`].
+pub fn encrypt_password(keys: &DerivedKeys, plaintext: &str) -> Result<Vec<u8>, DecryptError> {
+    let version = keys.current_version();
+    let derived_key = keys.key_for_version(version).ok_or_else(|| {
+        DecryptError::CryptoError(format!("No key available for version {}", version))
+    })?;
+
+    if derived_key.len() != KEY_LENGTH {
+        return Err(DecryptError::CryptoError(format!(
+            "Invalid key length: expected {}, got {}",
+            KEY_LENGTH,
+            derived_key.len()
+        )));
+    }
+
+    let encryptor = Aes128CbcEnc::new(derived_key.into(), IV.into());
+    let mut blob = version.as_bytes().to_vec();
+    blob.extend(encryptor.encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes()));
+    Ok(blob)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::encrypt;
 
     #[test]
     fn test_derive_key() {
@@ -82,4 +190,51 @@ mod tests {
     fn test_iv_length() {
         assert_eq!(IV.len(), 16);
     }
+
+    #[test]
+    fn test_version_prefix() {
+        assert_eq!(version_prefix(b"v10abc"), Some("v10"));
+        assert_eq!(version_prefix(b"v11"), Some("v11"));
+        assert_eq!(version_prefix(b"v1"), None);
+        assert_eq!(version_prefix(b"\x01\x02\x03\x04"), None);
+    }
+
+    #[test]
+    fn test_decrypt_selects_key_by_version() {
+        let keys = DerivedKeys::for_linux(Some("secret-service-password"));
+        let v10 = encrypt(&keys.v10, "v10", "peanut-pass");
+        let v11 = encrypt(keys.v11.as_ref().unwrap(), "v11", "keyring-pass");
+
+        assert_eq!(decrypt_password(&keys, &v10).unwrap(), "peanut-pass");
+        assert_eq!(decrypt_password(&keys, &v11).unwrap(), "keyring-pass");
+    }
+
+    #[test]
+    fn test_decrypt_v11_without_key_fails() {
+        let keys = DerivedKeys::for_linux(None);
+        let blob = encrypt(&keys.v10, "v11", "whatever");
+        assert!(decrypt_password(&keys, &blob).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_password_round_trip() {
+        let macos = DerivedKeys::for_macos("keychain-secret");
+        let blob = encrypt_password(&macos, "hunter2").unwrap();
+        assert_eq!(version_prefix(&blob), Some("v10"));
+        assert_eq!(blob, encrypt(&macos.v10, "v10", "hunter2"));
+        assert_eq!(decrypt_password(&macos, &blob).unwrap(), "hunter2");
+
+        let linux = DerivedKeys::for_linux(Some("secret-service-password"));
+        for password in ["", "pässwörd with spaces", "exactly16bytes!!"] {
+            let blob = encrypt_password(&linux, password).unwrap();
+            assert_eq!(version_prefix(&blob), Some("v11"));
+            assert_eq!(decrypt_password(&linux, &blob).unwrap(), password);
+        }
+
+        // Without a keyring, Chrome on Linux falls back to "v10"
+        let basic = DerivedKeys::for_linux(None);
+        let blob = encrypt_password(&basic, "peanut-pass").unwrap();
+        assert_eq!(version_prefix(&blob), Some("v10"));
+        assert_eq!(decrypt_password(&basic, &blob).unwrap(), "peanut-pass");
+    }
 }
@@ -0,0 +1,119 @@
+//! Minimal RFC 4180 CSV reading and writing
+//!
+//! Fields are quoted only when they contain a comma, quote, CR or LF, which
+//! matches the output of Chrome's own password exporter.
+
+use crate::models::DecryptError;
+
+/// Quote a field if it needs it
+pub fn escape_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Append one record, terminated by "\n"
+pub fn write_record<S: AsRef<str>>(output: &mut String, fields: &[S]) {
+    let line: Vec<String> = fields
+        .iter()
+        .map(|field| escape_field(field.as_ref()))
+        .collect();
+    output.push_str(&line.join(","));
+    output.push('\n');
+}
+
+/// Parse CSV text into records
+///
+/// Accepts "\n" and "\r\n" line endings, quoted fields spanning several
+/// lines and a leading UTF-8 BOM. Blank lines are skipped.
+pub fn parse_records(input: &str) -> Result<Vec<Vec<String>>, DecryptError> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '"' => {
+                return Err(DecryptError::FormatError(format!(
+                    "Unexpected quote on CSV line {}",
+                    line
+                )));
+            }
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) || record.len() > 1 {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(DecryptError::FormatError(
+            "Unterminated quoted CSV field".to_string(),
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_field() {
+        assert_eq!(escape_field("plain"), "plain");
+        assert_eq!(escape_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_parse_records() {
+        let input = "a,b,c\r\n\"x,y\",\"multi\nline\",\"q\"\"q\"\n\n1,,3";
+        let records = parse_records(input).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                vec!["a", "b", "c"],
+                vec!["x,y", "multi\nline", "q\"q"],
+                vec!["1", "", "3"],
+            ]
+        );
+        assert!(parse_records("\"open").is_err());
+    }
+}
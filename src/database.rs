@@ -1,144 +1,815 @@
-use crate::crypto::decrypt_password;
-use crate::models::{DecryptError, LoginInfo, QueryParams};
-use rusqlite::Connection;
-use std::fs::Permissions;
-use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use crate::crypto::{decrypt_password, version_prefix};
+use crate::models::{
+    AuthScheme, DecryptError, InsecureCredential, InsecurityType, LoginInfo, LoginRow,
+    PasswordStore, QueryParams, RowOutcome,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::fs::{DirBuilder, OpenOptions};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How a `Login Data` database is read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SnapshotMode {
+    /// Snapshot into a private temporary copy with SQLite's online backup API,
+    /// including changes still in the `-wal` file
+    #[default]
+    Backup,
+    /// Open the live file in place, read-only, with `immutable=1`; nothing is
+    /// copied, but changes not yet checkpointed from the `-wal` file are missed
+    Immutable,
+}
+
+/// Pages copied per backup step
+const BACKUP_PAGES_PER_STEP: i32 = 256;
+
+/// Busy/locked backup steps tolerated before falling back to a file copy
+const BACKUP_MAX_RETRIES: u32 = 20;
+
+/// Delay between busy backup steps
+const BACKUP_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// SQLite side files that hold committed or in-flight changes
+const SIDE_FILE_SUFFIXES: [&str; 2] = ["-wal", "-journal"];
+
+static SNAPSHOT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Private temporary copy of a database, removed on Drop
+pub struct TempDbCopy {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl TempDbCopy {
+    /// Path of the copied database file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDbCopy {
+    fn drop(&mut self) {
+        tracing::debug!("Cleaning up temporary copy: {}", self.dir.display());
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Open database, keeping its temporary copy (if any) alive
+pub struct DbSnapshot {
+    // Declared first so the connection closes before the copy is removed
+    pub conn: Connection,
+    _copy: Option<TempDbCopy>,
+}
+
+/// Open a consistent, read-only view of a `Login Data` database
+pub fn open_snapshot(db_path: &Path, mode: SnapshotMode) -> Result<DbSnapshot, DecryptError> {
+    if !db_path.exists() {
+        return Err(DecryptError::LoginDataNotFound);
+    }
+
+    match mode {
+        SnapshotMode::Backup => {
+            let copy = create_temp_db_copy(db_path)?;
+            let conn = Connection::open(copy.path())?;
+            Ok(DbSnapshot {
+                conn,
+                _copy: Some(copy),
+            })
+        }
+        SnapshotMode::Immutable => {
+            tracing::debug!(
+                "Opening database in place (immutable): {}",
+                db_path.display()
+            );
+            // file: URIs need an absolute path
+            let uri = url::Url::from_file_path(std::path::absolute(db_path)?).map_err(|_| {
+                DecryptError::IoError(format!("Invalid database path: {}", db_path.display()))
+            })?;
+            let conn = Connection::open_with_flags(
+                format!("{}?immutable=1", uri),
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+            )?;
+            Ok(DbSnapshot { conn, _copy: None })
+        }
+    }
+}
 
 /// Create a temporary copy of the database file
 ///
-/// Chrome locks the Login Data file while running, so we need to copy it first
-pub fn create_temp_db_copy(db_path: &Path) -> Result<std::path::PathBuf, DecryptError> {
+/// Chrome may be writing to Login Data while we read it, so a plain file copy
+/// can be torn or miss changes that only live in the `-wal` file. The copy is
+/// taken with SQLite's online backup API, which sees committed WAL content.
+/// If Chrome holds an exclusive lock, the database is copied together with
+/// its side files instead and the copy is checkpointed.
+pub fn create_temp_db_copy(db_path: &Path) -> Result<TempDbCopy, DecryptError> {
     tracing::debug!("Creating temporary copy of database: {}", db_path.display());
-    // Create temporary file
-    let mut temp_path = std::env::temp_dir();
-    temp_path.push(format!("chrome_login_data_{}.db", std::process::id()));
+    let copy = create_temp_dir()?;
+
+    match backup_database(db_path, copy.path()) {
+        Ok(()) => tracing::debug!("Database snapshot taken with backup API"),
+        Err(e) => {
+            tracing::debug!("Backup API failed ({}), copying database files", e);
+            copy_with_side_files(db_path, copy.path())?;
+        }
+    }
 
-    // Copy database file
-    std::fs::copy(db_path, &temp_path)?;
-    tracing::debug!("Temporary database created at: {}", temp_path.display());
+    tracing::debug!("Temporary database created at: {}", copy.path().display());
+    Ok(copy)
+}
+
+/// Create an empty 0700 directory (holding a 0600 database file) in the temp dir
+fn create_temp_dir() -> Result<TempDbCopy, DecryptError> {
+    let dir = std::env::temp_dir().join(format!(
+        "chrome_login_data_{}_{}",
+        std::process::id(),
+        SNAPSHOT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let path = dir.join("Login Data");
+    let copy = TempDbCopy { dir, path };
 
-    // Set strict file permissions (user read/write only)
-    let perms = Permissions::from_mode(0o600);
-    std::fs::set_permissions(&temp_path, perms)
-        .map_err(|e| DecryptError::IoError(format!("Failed to set permissions: {}", e)))?;
+    // Set strict file permissions (user read/write only) before any data lands
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(copy.path())
+        .map_err(|e| DecryptError::IoError(format!("Failed to create temporary file: {}", e)))?;
 
-    Ok(temp_path)
+    Ok(copy)
 }
 
-/// Query login information from database
-pub fn query_login_data(
-    db_path: &Path,
-    params: &QueryParams<'_>,
-) -> Result<Vec<LoginInfo>, DecryptError> {
-    tracing::debug!("Querying login data from: {}", db_path.display());
-    let temp_db = create_temp_db_copy(db_path)?;
+/// Snapshot `src` into `dst` with the online backup API
+pub(crate) fn backup_database(src: &Path, dst: &Path) -> Result<(), DecryptError> {
+    let source = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut target = Connection::open(dst)?;
+    let backup = Backup::new(&source, &mut target)?;
+
+    let mut retries = 0;
+    loop {
+        match backup.step(BACKUP_PAGES_PER_STEP)? {
+            StepResult::Done => return Ok(()),
+            StepResult::More => {}
+            StepResult::Busy | StepResult::Locked if retries < BACKUP_MAX_RETRIES => {
+                retries += 1;
+                std::thread::sleep(BACKUP_RETRY_DELAY);
+            }
+            StepResult::Busy | StepResult::Locked => {
+                return Err(DecryptError::DatabaseError(
+                    "Database is locked by another process".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
 
-    // Ensure temp file is deleted when function ends
-    let _guard = TempFileGuard(temp_db.clone());
+/// Copy `src` with its `-wal`/`-journal` files, then checkpoint the copy
+///
+/// Opening the copy replays the WAL (or rolls back a hot journal), so the
+/// result matches what SQLite would read from the original.
+fn copy_with_side_files(src: &Path, dst: &Path) -> Result<(), DecryptError> {
+    std::fs::copy(src, dst)?;
+    for suffix in SIDE_FILE_SUFFIXES {
+        let side = append_to_path(src, suffix);
+        if side.exists() {
+            std::fs::copy(&side, append_to_path(dst, suffix))?;
+        }
+    }
 
-    let conn = Connection::open(&temp_db)?;
+    let conn = Connection::open(dst)?;
+    let _: (i64, i64, i64) = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?;
+    Ok(())
+}
 
-    let mut result = Vec::new();
+/// `path` with `suffix` appended to its file name
+fn append_to_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
 
-    // Use parameterized query to prevent SQL injection
-    if let Some(keyword) = &params.keyword {
-        tracing::debug!("Querying with keyword filter: '{}'", keyword);
-        // Use parameterized query
-        let search_pattern = format!("%{}%", keyword);
-        let mut stmt = conn.prepare(
-            "SELECT action_url, username_value, password_value FROM logins WHERE action_url LIKE ?",
-        )?;
-
-        let rows = stmt.query_map([&search_pattern], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, Vec<u8>>(2)?,
-            ))
-        })?;
-
-        for row in rows {
-            let (url, username, encrypted_pwd) = row?;
-
-            // Skip empty passwords
-            if encrypted_pwd.is_empty() {
-                continue;
+/// Microseconds between the WebKit epoch (1601-01-01) and the Unix epoch
+const WEBKIT_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+/// Convert a Chrome (WebKit epoch, microseconds) timestamp to UTC
+///
+/// Chrome stores 0 for "never", which maps to `None`.
+pub fn webkit_timestamp_to_datetime(timestamp: i64) -> Option<DateTime<Utc>> {
+    if timestamp <= 0 {
+        return None;
+    }
+    DateTime::from_timestamp_micros(timestamp - WEBKIT_EPOCH_OFFSET_MICROS)
+}
+
+/// Convert a UTC time to a Chrome (WebKit epoch, microseconds) timestamp;
+/// `None` maps to 0 ("never")
+pub fn datetime_to_webkit_timestamp(time: Option<DateTime<Utc>>) -> i64 {
+    time.map_or(0, |time| {
+        time.timestamp_micros() + WEBKIT_EPOCH_OFFSET_MICROS
+    })
+}
+
+/// `logins` columns read by [`query_login_data`], with a fallback for
+/// databases written by Chrome versions that predate the column
+const LOGIN_COLUMNS: &[(&str, &str)] = &[
+    ("origin_url", "''"),
+    ("action_url", "''"),
+    ("username_value", "''"),
+    ("password_value", "X''"),
+    ("signon_realm", "''"),
+    ("username_element", "''"),
+    ("password_element", "''"),
+    ("date_created", "0"),
+    ("date_last_used", "0"),
+    ("date_password_modified", "0"),
+    ("times_used", "0"),
+    ("blacklisted_by_user", "0"),
+    ("scheme", "0"),
+    ("id", "0"),
+];
+
+/// Build the `SELECT` list for [`LOGIN_COLUMNS`]
+fn login_select_list(conn: &Connection) -> Result<String, DecryptError> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('logins')")?;
+    let existing: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<_, _>>()?;
+
+    let columns: Vec<String> = LOGIN_COLUMNS
+        .iter()
+        .map(|(name, fallback)| {
+            if existing.iter().any(|c| c == name) {
+                format!("COALESCE({}, {})", name, fallback)
+            } else {
+                tracing::debug!("logins table has no '{}' column", name);
+                format!("{} AS {}", fallback, name)
             }
+        })
+        .collect();
 
-            // Decrypt password
-            let password = match decrypt_password(params.derived_key, &encrypted_pwd) {
-                Ok(pwd) => pwd,
-                Err(e) => {
-                    tracing::debug!("Failed to decrypt password for {}: {}", url, e);
-                    continue; // Skip entries that failed to decrypt
-                }
-            };
+    Ok(columns.join(", "))
+}
+
+/// Read a row selected with [`login_select_list`]; the password is left empty
+///
+/// Returns the login, its encrypted password and its `id`.
+fn read_login_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<(LoginInfo, Vec<u8>, i64)> {
+    let origin_url: String = row.get(0)?;
+    let action_url: String = row.get(1)?;
+    let url = if origin_url.is_empty() {
+        action_url.clone()
+    } else {
+        origin_url.clone()
+    };
 
-            // Skip empty URLs
-            if url.is_empty() {
+    let info = LoginInfo {
+        url,
+        username: row.get(2)?,
+        password: String::new(),
+        origin_url,
+        action_url,
+        signon_realm: row.get(4)?,
+        username_element: row.get(5)?,
+        password_element: row.get(6)?,
+        date_created: webkit_timestamp_to_datetime(row.get(7)?),
+        date_last_used: webkit_timestamp_to_datetime(row.get(8)?),
+        date_password_modified: webkit_timestamp_to_datetime(row.get(9)?),
+        times_used: row.get(10)?,
+        blacklisted_by_user: row.get::<_, i64>(11)? != 0,
+        scheme: AuthScheme::from_db(row.get(12)?),
+        // Set by the caller, which knows which database this is
+        store: PasswordStore::Local,
+        note: String::new(),
+        insecure: Vec::new(),
+    };
+
+    Ok((info, row.get(3)?, row.get(13)?))
+}
+
+/// Decrypt the notes of `password_notes`, keyed by the login's `id`
+///
+/// A login may have several notes; the main note (empty `key`) comes first
+/// and the others are appended as "key: value" lines. Databases without the
+/// table have no notes; notes that fail to decrypt are skipped.
+fn read_password_notes(
+    conn: &Connection,
+    derived_keys: &crate::crypto::DerivedKeys,
+) -> Result<HashMap<i64, String>, DecryptError> {
+    if !has_table(conn, "password_notes")? {
+        return Ok(HashMap::new());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT parent_id, key, value FROM password_notes
+         WHERE value IS NOT NULL ORDER BY parent_id, key",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Vec<u8>>(2)?,
+        ))
+    })?;
+
+    let mut notes: HashMap<i64, String> = HashMap::new();
+    for row in rows {
+        let (parent_id, key, encrypted) = row?;
+        if encrypted.is_empty() {
+            continue;
+        }
+        let value = match decrypt_password(derived_keys, &encrypted) {
+            Ok(value) if value.is_empty() => continue,
+            Ok(value) => value,
+            Err(e) => {
+                tracing::debug!("Failed to decrypt note of login {}: {}", parent_id, e);
                 continue;
             }
+        };
 
-            result.push(LoginInfo {
-                url,
-                username,
-                password,
-            });
+        let note = notes.entry(parent_id).or_default();
+        if !note.is_empty() {
+            note.push('\n');
+        }
+        if key.is_empty() {
+            note.push_str(&value);
+        } else {
+            note.push_str(&format!("{}: {}", key, value));
+        }
+    }
+
+    Ok(notes)
+}
+
+/// Password Checkup results per login id, from `insecure_credentials`
+///
+/// Databases written by Chrome versions without Password Checkup have no
+/// such table and no results.
+fn read_insecure_credentials(
+    conn: &Connection,
+) -> Result<HashMap<i64, Vec<InsecureCredential>>, DecryptError> {
+    if !has_table(conn, "insecure_credentials")? {
+        return Ok(HashMap::new());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT parent_id, insecurity_type, create_time, is_muted FROM insecure_credentials
+         ORDER BY parent_id, insecurity_type",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            InsecureCredential {
+                insecurity_type: InsecurityType::from_db(row.get(1)?),
+                create_time: webkit_timestamp_to_datetime(row.get(2)?),
+                is_muted: row.get::<_, i64>(3)? != 0,
+            },
+        ))
+    })?;
+
+    let mut credentials: HashMap<i64, Vec<InsecureCredential>> = HashMap::new();
+    for row in rows {
+        let (parent_id, credential) = row?;
+        credentials.entry(parent_id).or_default().push(credential);
+    }
+    Ok(credentials)
+}
+
+/// Whether the database has a table called `name`
+pub(crate) fn has_table(conn: &Connection, name: &str) -> Result<bool, DecryptError> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [name],
+        |row| row.get(0),
+    )?)
+}
+
+/// Query every login row from database, with its decryption outcome
+///
+/// Rows that cannot be shown (undecryptable, empty password, "never save")
+/// are returned too, so callers can report them instead of losing them.
+pub fn query_login_rows(
+    db_path: &Path,
+    params: &QueryParams<'_>,
+) -> Result<Vec<LoginRow>, DecryptError> {
+    tracing::debug!("Querying login data from: {}", db_path.display());
+    // The snapshot's temporary copy is deleted when it goes out of scope
+    let snapshot = open_snapshot(db_path, params.snapshot)?;
+    let conn = &snapshot.conn;
+    let select_list = login_select_list(conn)?;
+
+    // Use parameterized query to prevent SQL injection
+    let (sql, search_pattern) = match &params.keyword {
+        Some(keyword) => {
+            tracing::debug!("Querying with keyword filter: '{}'", keyword);
+            (
+                format!(
+                    "SELECT {} FROM logins WHERE origin_url LIKE ?1 OR action_url LIKE ?1 \
+                     OR signon_realm LIKE ?1",
+                    select_list
+                ),
+                Some(format!("%{}%", keyword)),
+            )
         }
-    } else {
         // No keyword filter, query all records
-        let mut stmt =
-            conn.prepare("SELECT action_url, username_value, password_value FROM logins")?;
-
-        let rows = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, Vec<u8>>(2)?,
-            ))
-        })?;
-
-        for row in rows {
-            let (url, username, encrypted_pwd) = row?;
-
-            // Skip empty passwords
-            if encrypted_pwd.is_empty() {
-                continue;
-            }
+        None => (format!("SELECT {} FROM logins", select_list), None),
+    };
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = match &search_pattern {
+        Some(pattern) => stmt.query_map([pattern], read_login_row)?,
+        None => stmt.query_map([], read_login_row)?,
+    };
 
-            // Decrypt password
-            let password = match decrypt_password(params.derived_key, &encrypted_pwd) {
-                Ok(pwd) => pwd,
+    let mut notes = read_password_notes(conn, params.derived_keys)?;
+    let mut insecure = read_insecure_credentials(conn)?;
+    let mut result = Vec::new();
+    for row in rows {
+        let (mut info, encrypted_pwd, id) = row?;
+        info.note = notes.remove(&id).unwrap_or_default();
+        info.insecure = insecure.remove(&id).unwrap_or_default();
+
+        let outcome = if info.blacklisted_by_user {
+            RowOutcome::NeverSave
+        } else if encrypted_pwd.is_empty() {
+            RowOutcome::EmptyPassword
+        } else {
+            match decrypt_password(params.derived_keys, &encrypted_pwd) {
+                Ok(pwd) if pwd.is_empty() => RowOutcome::EmptyPassword,
+                Ok(pwd) => {
+                    info.password = pwd;
+                    RowOutcome::Decrypted
+                }
                 Err(e) => {
-                    tracing::debug!("Failed to decrypt password for {}: {}", url, e);
-                    continue; // Skip entries that failed to decrypt
+                    tracing::debug!("Failed to decrypt password for {}: {}", info.url, e);
+                    RowOutcome::Undecryptable {
+                        reason: e.to_string(),
+                        version_prefix: version_prefix(&encrypted_pwd).map(str::to_string),
+                        ciphertext_len: encrypted_pwd.len(),
+                    }
                 }
-            };
-
-            // Skip empty URLs
-            if url.is_empty() {
-                continue;
             }
+        };
 
-            result.push(LoginInfo {
-                url,
-                username,
-                password,
-            });
-        }
+        result.push(LoginRow { info, outcome });
     }
 
-    tracing::debug!("Query completed, found {} login(s)", result.len());
+    tracing::debug!("Query completed, read {} row(s)", result.len());
     Ok(result)
 }
 
-/// Temporary file guard, ensures temp file is deleted on Drop
-struct TempFileGuard(std::path::PathBuf);
+/// Query decrypted login information from database
+pub fn query_login_data(
+    db_path: &Path,
+    params: &QueryParams<'_>,
+) -> Result<Vec<LoginInfo>, DecryptError> {
+    let rows = query_login_rows(db_path, params)?;
+    Ok(rows
+        .into_iter()
+        .filter(|row| row.outcome.is_decrypted())
+        .map(|row| row.info)
+        .collect())
+}
 
-impl Drop for TempFileGuard {
-    fn drop(&mut self) {
-        tracing::debug!("Cleaning up temporary file: {}", self.0.display());
-        let _ = std::fs::remove_file(&self.0);
+/// Read up to `limit` non-empty encrypted password blobs
+///
+/// Used to check whether a candidate key still matches a profile.
+pub fn sample_encrypted_passwords(
+    db_path: &Path,
+    limit: usize,
+) -> Result<Vec<Vec<u8>>, DecryptError> {
+    let snapshot = open_snapshot(db_path, SnapshotMode::Backup)?;
+    let mut stmt = snapshot
+        .conn
+        .prepare("SELECT password_value FROM logins WHERE length(password_value) > 0 LIMIT ?")?;
+    let rows = stmt.query_map([limit as i64], |row| row.get::<_, Vec<u8>>(0))?;
+
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::DerivedKeys;
+    use crate::test_support::{TestLogin, create_login_db, insert_login};
+
+    fn usernames(db_path: &Path, keys: &DerivedKeys, snapshot: SnapshotMode) -> Vec<String> {
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: keys,
+            snapshot,
+        };
+        query_login_data(db_path, &params)
+            .unwrap()
+            .into_iter()
+            .map(|login| login.username)
+            .collect()
+    }
+
+    #[test]
+    fn test_snapshot_reads_uncheckpointed_wal_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let keys = DerivedKeys::for_linux(None);
+        create_login_db(
+            &db_path,
+            &keys,
+            &[TestLogin::new("https://old.example/", "alice", "pw")],
+        );
+
+        // Keep the writer open with checkpointing disabled, like a running Chrome
+        let writer = Connection::open(&db_path).unwrap();
+        writer
+            .query_row("PRAGMA journal_mode=WAL", [], |row| row.get::<_, String>(0))
+            .unwrap();
+        writer.execute_batch("PRAGMA wal_autocheckpoint=0").unwrap();
+        insert_login(
+            &writer,
+            &keys,
+            &TestLogin::new("https://new.example/", "bob", "pw"),
+        );
+        let wal = append_to_path(&db_path, "-wal");
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+
+        assert_eq!(
+            usernames(&db_path, &keys, SnapshotMode::Backup),
+            ["alice", "bob"]
+        );
+
+        // Both the backup API and the fallback used when Chrome holds an
+        // exclusive lock must see the WAL content
+        let count_rows = |copy: &TempDbCopy| -> i64 {
+            let conn = Connection::open(copy.path()).unwrap();
+            conn.query_row("SELECT COUNT(*) FROM logins", [], |row| row.get(0))
+                .unwrap()
+        };
+        let backup = create_temp_dir().unwrap();
+        backup_database(&db_path, backup.path()).unwrap();
+        assert_eq!(count_rows(&backup), 2);
+
+        let copy = create_temp_dir().unwrap();
+        copy_with_side_files(&db_path, copy.path()).unwrap();
+        assert_eq!(count_rows(&copy), 2);
+        drop(writer);
+    }
+
+    #[test]
+    fn test_immutable_snapshot_reads_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let keys = DerivedKeys::for_linux(None);
+        create_login_db(
+            &db_path,
+            &keys,
+            &[TestLogin::new("https://example.com/", "alice", "pw")],
+        );
+
+        assert_eq!(
+            usernames(&db_path, &keys, SnapshotMode::Immutable),
+            ["alice"]
+        );
+        assert!(!append_to_path(&db_path, "-wal").exists());
+        assert!(!append_to_path(&db_path, "-journal").exists());
+    }
+
+    #[test]
+    fn test_immutable_snapshot_with_relative_path() {
+        // Relative to the working directory, like `--login-data ../Login\ Data`
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        let mut db_path: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+        db_path.push(dir.path().strip_prefix("/").unwrap());
+        db_path.push("Login Data");
+        assert!(db_path.is_relative());
+        let keys = DerivedKeys::for_linux(None);
+        create_login_db(
+            &db_path,
+            &keys,
+            &[TestLogin::new("https://example.com/", "alice", "pw")],
+        );
+
+        assert_eq!(
+            usernames(&db_path, &keys, SnapshotMode::Immutable),
+            ["alice"]
+        );
+    }
+
+    #[test]
+    fn test_query_reads_extended_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let keys = DerivedKeys::for_linux(None);
+        create_login_db(
+            &db_path,
+            &keys,
+            &[
+                TestLogin::new("https://example.com/", "alice", "pw")
+                    .action_url("https://example.com/login")
+                    .dates(13_348_540_800_000_000, 0)
+                    .times_used(7),
+                // Federated/Android entries may only have an action URL
+                TestLogin::new("", "bob", "pw2")
+                    .action_url("https://accounts.example.org/")
+                    .signon_realm("android://hash@com.example.app/"),
+            ],
+        );
+
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: &keys,
+            snapshot: SnapshotMode::Backup,
+        };
+        let logins = query_login_data(&db_path, &params).unwrap();
+
+        assert_eq!(logins.len(), 2);
+        assert_eq!(logins[0].url, "https://example.com/");
+        assert_eq!(logins[0].action_url, "https://example.com/login");
+        assert_eq!(logins[0].times_used, 7);
+        assert_eq!(
+            logins[0].date_created.unwrap().to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert_eq!(logins[0].date_password_modified, None);
+        assert_eq!(logins[1].url, "https://accounts.example.org/");
+        assert_eq!(logins[1].signon_realm, "android://hash@com.example.app/");
+
+        let params = QueryParams {
+            keyword: Some("com.example.app".to_string()),
+            derived_keys: &keys,
+            snapshot: SnapshotMode::Backup,
+        };
+        let logins = query_login_data(&db_path, &params).unwrap();
+        assert_eq!(logins.len(), 1);
+        assert_eq!(logins[0].username, "bob");
+    }
+
+    #[test]
+    fn test_query_joins_password_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let keys = DerivedKeys::for_linux(Some("secret"));
+        create_login_db(
+            &db_path,
+            &keys,
+            &[
+                TestLogin::new("https://a.example/", "alice", "pw"),
+                TestLogin::new("https://b.example/", "bob", "pw2")
+                    .version("v11")
+                    .note("recovery: 1111-2222"),
+            ],
+        );
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute(
+            "INSERT INTO password_notes (parent_id, key, value, date_created)
+             VALUES (2, 'pin', ?1, 0)",
+            [crate::test_support::encrypt(
+                keys.key_for_version("v10").unwrap(),
+                "v10",
+                "0000",
+            )],
+        )
+        .unwrap();
+        drop(conn);
+
+        let logins = usernames_and_notes(&db_path, &keys);
+        assert_eq!(
+            logins,
+            vec![
+                ("alice".to_string(), String::new()),
+                (
+                    "bob".to_string(),
+                    "recovery: 1111-2222\npin: 0000".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_joins_insecure_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let keys = DerivedKeys::for_linux(None);
+        create_login_db(
+            &db_path,
+            &keys,
+            &[
+                TestLogin::new("https://a.example/", "alice", "pw"),
+                TestLogin::new("https://b.example/", "bob", "pw"),
+                TestLogin::new("https://c.example/", "carol", "pw"),
+            ],
+        );
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "INSERT INTO insecure_credentials (parent_id, insecurity_type, create_time, is_muted)
+             VALUES (2, 0, 13348540800000000, 0), (2, 3, 13348540800000000, 0),
+                    (3, 1, 13348540800000000, 1);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: &keys,
+            snapshot: SnapshotMode::Backup,
+        };
+        let logins = query_login_data(&db_path, &params).unwrap();
+        assert!(logins[0].insecure.is_empty());
+        assert_eq!(
+            logins[1].insecure,
+            vec![
+                InsecureCredential {
+                    insecurity_type: InsecurityType::Leaked,
+                    create_time: webkit_timestamp_to_datetime(13_348_540_800_000_000),
+                    is_muted: false,
+                },
+                InsecureCredential {
+                    insecurity_type: InsecurityType::Reused,
+                    create_time: webkit_timestamp_to_datetime(13_348_540_800_000_000),
+                    is_muted: false,
+                },
+            ]
+        );
+        let compromised: Vec<_> = logins.iter().map(LoginInfo::is_compromised).collect();
+        assert_eq!(compromised, [false, true, false]);
+    }
+
+    fn usernames_and_notes(db_path: &Path, keys: &DerivedKeys) -> Vec<(String, String)> {
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: keys,
+            snapshot: SnapshotMode::Backup,
+        };
+        query_login_data(db_path, &params)
+            .unwrap()
+            .into_iter()
+            .map(|login| (login.username, login.note))
+            .collect()
+    }
+
+    #[test]
+    fn test_query_login_rows_reports_skipped_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let keys = DerivedKeys::for_linux(Some("secret"));
+        create_login_db(
+            &db_path,
+            &keys,
+            &[
+                TestLogin::new("https://ok.example/", "alice", "pw"),
+                TestLogin::new("https://v11.example/", "bob", "pw").version("v11"),
+                TestLogin::new("", "carol", "pw").action_url(""),
+            ],
+        );
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "INSERT INTO logins (origin_url, signon_realm, password_value, date_created,
+                blacklisted_by_user, scheme)
+             VALUES ('https://never.example/', 'https://never.example/', X'', 0, 1, 0),
+                    ('https://empty.example/', 'https://empty.example/', X'', 0, 0, 0);",
+        )
+        .unwrap();
+
+        // Wrong v11 key: only that row fails
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: &DerivedKeys::for_linux(Some("other")),
+            snapshot: SnapshotMode::Backup,
+        };
+        let rows = query_login_rows(&db_path, &params).unwrap();
+        let outcomes: Vec<_> = rows.iter().map(|r| &r.outcome).collect();
+
+        assert_eq!(rows.len(), 5);
+        assert_eq!(outcomes[0], &RowOutcome::Decrypted);
+        assert!(matches!(
+            outcomes[1],
+            RowOutcome::Undecryptable { version_prefix: Some(v), ciphertext_len: 19, .. } if v == "v11"
+        ));
+        // Empty URLs are kept
+        assert_eq!(outcomes[2], &RowOutcome::Decrypted);
+        assert_eq!(rows[2].info.password, "pw");
+        assert_eq!(outcomes[3], &RowOutcome::NeverSave);
+        assert_eq!(outcomes[4], &RowOutcome::EmptyPassword);
+    }
+
+    #[test]
+    fn test_webkit_timestamp_to_datetime() {
+        assert_eq!(webkit_timestamp_to_datetime(0), None);
+        // 2024-01-01T00:00:00Z
+        let dt = webkit_timestamp_to_datetime(13_348_540_800_000_000).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(
+            datetime_to_webkit_timestamp(Some(dt)),
+            13_348_540_800_000_000
+        );
+        assert_eq!(datetime_to_webkit_timestamp(None), 0);
     }
 }
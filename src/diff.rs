@@ -0,0 +1,174 @@
+//! Compare two snapshots of saved logins
+//!
+//! Logins are matched by signon realm and username, the key Chrome itself
+//! uses for a saved credential. Records without a signon realm (Chrome CSV
+//! exports) get the realm Chrome derives from their URL on import.
+
+use crate::import::signon_realm;
+use crate::models::LoginInfo;
+use std::collections::{HashMap, HashSet};
+
+/// Logins added, removed and with a changed password between two snapshots
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct LoginDiff {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub changed: Vec<DiffEntry>,
+    /// Logins saved in both with the same password
+    pub unchanged: usize,
+}
+
+impl LoginDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// One login that differs between the snapshots
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DiffEntry {
+    pub signon_realm: String,
+    pub username: String,
+    pub url: String,
+    /// Old and new password of a changed login, only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passwords: Option<PasswordChange>,
+}
+
+/// Password of a login before and after the change
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PasswordChange {
+    pub old: String,
+    pub new: String,
+}
+
+/// Compare the logins of snapshot `old` with those of snapshot `new`
+///
+/// Changed passwords are only included with `show_passwords`. When a
+/// snapshot holds the same key twice (e.g. in the local and the account
+/// store), the first login counts. Removed and changed logins keep the order
+/// of `old`, added ones the order of `new`.
+pub fn diff_logins(old: &[LoginInfo], new: &[LoginInfo], show_passwords: bool) -> LoginDiff {
+    let old = keyed(old);
+    let new = keyed(new);
+    let new_index: HashMap<_, _> = new.iter().map(|(key, info)| (key, *info)).collect();
+    let old_index: HashMap<_, _> = old.iter().map(|(key, info)| (key, *info)).collect();
+
+    let mut diff = LoginDiff::default();
+    for (key, before) in &old {
+        match new_index.get(key) {
+            None => diff.removed.push(entry(key, before, None)),
+            Some(after) if after.password != before.password => {
+                let passwords = show_passwords.then(|| PasswordChange {
+                    old: before.password.clone(),
+                    new: after.password.clone(),
+                });
+                diff.changed.push(entry(key, after, passwords));
+            }
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    for (key, after) in &new {
+        if !old_index.contains_key(key) {
+            diff.added.push(entry(key, after, None));
+        }
+    }
+    diff
+}
+
+/// Logins with their (signon realm, username) key, first of each key only
+fn keyed(logins: &[LoginInfo]) -> Vec<((String, String), &LoginInfo)> {
+    let mut seen = HashSet::new();
+    logins
+        .iter()
+        .map(|info| {
+            let realm = if !info.signon_realm.is_empty() {
+                info.signon_realm.clone()
+            } else if !info.origin_url.is_empty() {
+                signon_realm(&info.origin_url)
+            } else {
+                signon_realm(&info.url)
+            };
+            ((realm, info.username.clone()), info)
+        })
+        .filter(|(key, _)| seen.insert(key.clone()))
+        .collect()
+}
+
+fn entry(key: &(String, String), info: &LoginInfo, passwords: Option<PasswordChange>) -> DiffEntry {
+    DiffEntry {
+        signon_realm: key.0.clone(),
+        username: key.1.clone(),
+        url: info.url.clone(),
+        passwords,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login(realm: &str, url: &str, username: &str, password: &str) -> LoginInfo {
+        LoginInfo {
+            url: url.to_string(),
+            origin_url: url.to_string(),
+            signon_realm: realm.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_logins() {
+        let old = vec![
+            login(
+                "https://github.com/",
+                "https://github.com/login",
+                "alice",
+                "a1",
+            ),
+            login("https://example.com/", "https://example.com/", "bob", "b1"),
+            login(
+                "https://gone.example/",
+                "https://gone.example/",
+                "carol",
+                "c1",
+            ),
+        ];
+        // As read back from a Chrome CSV export: no signon realm
+        let new = vec![
+            login("", "https://github.com/login", "alice", "a1"),
+            login("", "https://example.com/", "bob", "b2"),
+            login("", "https://example.com/", "dave", "d1"),
+            login("", "https://example.com/", "dave", "d2"),
+        ];
+
+        let diff = diff_logins(&old, &new, false);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].username, "carol");
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(
+            (
+                diff.added[0].signon_realm.as_str(),
+                diff.added[0].username.as_str()
+            ),
+            ("https://example.com/", "dave")
+        );
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].username, "bob");
+        assert_eq!(diff.changed[0].passwords, None);
+        assert!(!serde_json::to_string(&diff).unwrap().contains("b2"));
+
+        let shown = diff_logins(&old, &new, true);
+        assert_eq!(
+            shown.changed[0].passwords,
+            Some(PasswordChange {
+                old: "b1".to_string(),
+                new: "b2".to_string(),
+            })
+        );
+        assert!(diff_logins(&old, &old, true).is_empty());
+    }
+}
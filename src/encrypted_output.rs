@@ -0,0 +1,206 @@
+//! age encryption of exports and query output
+//!
+//! Output is encrypted in memory before it is written anywhere, either to
+//! X25519 recipients (`age1...`) or to a passphrase (scrypt). Output for a
+//! terminal is ASCII-armored; `decrypt_output` accepts both encodings.
+
+use crate::models::DecryptError;
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+use age::secrecy::SecretString;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// First line of ASCII-armored output
+const ARMOR_BEGIN: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+/// Last line of ASCII-armored output
+const ARMOR_END: &[u8] = b"-----END AGE ENCRYPTED FILE-----";
+
+/// Who can decrypt the output
+#[derive(Clone)]
+pub enum OutputEncryption {
+    /// age X25519 recipients
+    Recipients(Vec<age::x25519::Recipient>),
+    /// A passphrase (scrypt recipient)
+    Passphrase(String),
+}
+
+/// Key for decrypting encrypted output
+pub enum DecryptionKey {
+    /// Contents of an age identity file (`AGE-SECRET-KEY-1...` lines)
+    Identities(String),
+    /// The passphrase the output was encrypted with
+    Passphrase(String),
+}
+
+/// Parse an `age1...` recipient
+pub fn parse_recipient(value: &str) -> Result<age::x25519::Recipient, DecryptError> {
+    age::x25519::Recipient::from_str(value.trim())
+        .map_err(|e| DecryptError::FormatError(format!("Invalid age recipient '{}': {}", value, e)))
+}
+
+/// Whether `data` is an age file (binary or armored)
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(b"age-encryption.org/") || find(data, ARMOR_BEGIN).is_some()
+}
+
+/// Encrypt `plaintext`; with `armor` the result is PEM-style ASCII
+pub fn encrypt_output(
+    plaintext: &[u8],
+    encryption: &OutputEncryption,
+    armor: bool,
+) -> Result<Vec<u8>, DecryptError> {
+    match encryption {
+        OutputEncryption::Recipients(recipients) => {
+            let recipients = recipients
+                .iter()
+                .map(|recipient| recipient as &dyn age::Recipient);
+            encrypt_to(recipients, plaintext, armor)
+        }
+        OutputEncryption::Passphrase(passphrase) => {
+            let recipient = age::scrypt::Recipient::new(SecretString::from(passphrase.clone()));
+            encrypt_to(
+                std::iter::once(&recipient as &dyn age::Recipient),
+                plaintext,
+                armor,
+            )
+        }
+    }
+}
+
+/// Decrypt output written by `encrypt_output` (binary or armored)
+///
+/// Text around an armored block, such as log lines captured along with the
+/// query output, is skipped.
+pub fn decrypt_output(ciphertext: &[u8], key: &DecryptionKey) -> Result<Vec<u8>, DecryptError> {
+    let ciphertext = match find(ciphertext, ARMOR_BEGIN) {
+        Some(start) => {
+            let block = &ciphertext[start..];
+            match find(block, ARMOR_END) {
+                Some(end) => &block[..end + ARMOR_END.len()],
+                None => block,
+            }
+        }
+        None => ciphertext,
+    };
+    let decryptor = age::Decryptor::new_buffered(ArmoredReader::new(ciphertext))
+        .map_err(|e| DecryptError::FormatError(format!("Not an age encrypted file: {}", e)))?;
+
+    let identities: Vec<Box<dyn age::Identity>> = match key {
+        DecryptionKey::Identities(_) if decryptor.is_scrypt() => {
+            return Err(DecryptError::CryptoError(
+                "The file is encrypted with a passphrase, not to a recipient".to_string(),
+            ));
+        }
+        DecryptionKey::Passphrase(_) if !decryptor.is_scrypt() => {
+            return Err(DecryptError::CryptoError(
+                "The file is encrypted to recipients and needs an identity file".to_string(),
+            ));
+        }
+        DecryptionKey::Identities(contents) => age::IdentityFile::from_buffer(contents.as_bytes())?
+            .into_identities()
+            .map_err(|e| DecryptError::FormatError(format!("Invalid identity file: {}", e)))?,
+        DecryptionKey::Passphrase(passphrase) => vec![Box::new(age::scrypt::Identity::new(
+            SecretString::from(passphrase.clone()),
+        ))],
+    };
+
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref()))
+        .map_err(|e| DecryptError::CryptoError(format!("age decryption failed: {}", e)))?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Encrypt to age X25519 recipients, binary encoding
+pub(crate) fn age_encrypt(
+    recipients: &[age::x25519::Recipient],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, DecryptError> {
+    encrypt_output(
+        plaintext,
+        &OutputEncryption::Recipients(recipients.to_vec()),
+        false,
+    )
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn encrypt_to<'a>(
+    recipients: impl Iterator<Item = &'a dyn age::Recipient>,
+    plaintext: &[u8],
+    armor: bool,
+) -> Result<Vec<u8>, DecryptError> {
+    let encryptor = age::Encryptor::with_recipients(recipients)
+        .map_err(|e| DecryptError::CryptoError(format!("age encryption failed: {}", e)))?;
+    let format = if armor {
+        Format::AsciiArmor
+    } else {
+        Format::Binary
+    };
+
+    let mut ciphertext = Vec::new();
+    let armored = ArmoredWriter::wrap_output(&mut ciphertext, format)?;
+    let mut writer = encryptor.wrap_output(armored)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?.finish()?;
+    Ok(ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    #[test]
+    fn test_recipient_round_trip() {
+        let identity = age::x25519::Identity::generate();
+        let recipient = parse_recipient(&identity.to_public().to_string()).unwrap();
+        let encryption = OutputEncryption::Recipients(vec![recipient]);
+        let key = DecryptionKey::Identities(format!(
+            "# created: test\n{}\n",
+            identity.to_string().expose_secret()
+        ));
+
+        let armored = encrypt_output(b"url,password\n", &encryption, true).unwrap();
+        assert!(armored.starts_with(ARMOR_BEGIN));
+        assert!(is_encrypted(&armored) && !is_encrypted(b"url,password\n"));
+        let mut captured = b"INFO Starting password decryption\n".to_vec();
+        captured.extend_from_slice(&armored);
+        captured.extend_from_slice(b"INFO Password decryption completed successfully\n");
+        assert_eq!(decrypt_output(&captured, &key).unwrap(), b"url,password\n");
+
+        let binary = encrypt_output(b"url,password\n", &encryption, false).unwrap();
+        assert!(binary.starts_with(b"age-encryption.org/v1") && is_encrypted(&binary));
+        assert_eq!(decrypt_output(&binary, &key).unwrap(), b"url,password\n");
+
+        let other = age::x25519::Identity::generate();
+        let wrong = DecryptionKey::Identities(other.to_string().expose_secret().to_string());
+        assert!(decrypt_output(&binary, &wrong).is_err());
+        assert!(parse_recipient("age1notakey").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        // Low scrypt work factor, the default takes seconds in debug builds
+        let mut recipient = age::scrypt::Recipient::new(SecretString::from("hunter2".to_string()));
+        recipient.set_work_factor(10);
+        let ciphertext = encrypt_to(
+            std::iter::once(&recipient as &dyn age::Recipient),
+            b"[]",
+            false,
+        )
+        .unwrap();
+
+        let key = DecryptionKey::Passphrase("hunter2".to_string());
+        assert_eq!(decrypt_output(&ciphertext, &key).unwrap(), b"[]");
+        let wrong = DecryptionKey::Passphrase("hunter3".to_string());
+        assert!(decrypt_output(&ciphertext, &wrong).is_err());
+        let identity = DecryptionKey::Identities(String::new());
+        assert!(decrypt_output(&ciphertext, &identity).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::bitwarden::format_bitwarden_json;
+use crate::browser::Browser;
+use crate::chrome_csv::format_chrome_csv;
+use crate::firefox_csv::format_firefox_csv;
+use crate::kdbx::{KdbxKey, KdfParams, format_kdbx};
+use crate::models::{DecryptError, LoginInfo, LoginInfoWithProfile};
+use crate::onepux::format_1pux;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+/// Supported export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// JSON array of logins with their profile
+    Json,
+    /// Chrome / Google Password Manager CSV (name,url,username,password,note)
+    ChromeCsv,
+    /// Firefox CSV (url,username,password,httpRealm,formActionOrigin,guid,
+    /// timeCreated,timeLastUsed,timePasswordChanged)
+    FirefoxCsv,
+    /// Bitwarden unencrypted JSON
+    Bitwarden,
+    /// 1Password Unencrypted Export (1PUX), a vault per profile
+    #[value(name = "1pux")]
+    OnePux,
+    /// KeePass KDBX 4 database (encrypted)
+    Kdbx,
+    /// pass / gopass store: one GPG or age encrypted file per login,
+    /// written into the store directory given with --output
+    Pass,
+}
+
+/// Settings shared by the export formats
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Put each profile into its own folder (formats with folders only)
+    pub folders: bool,
+    /// Master password and/or key file of a KDBX export
+    pub kdbx_key: Option<KdbxKey>,
+}
+
+/// Serialize logins in the given format
+pub fn export_logins(
+    logins: &[LoginInfoWithProfile],
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, DecryptError> {
+    match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(logins)
+            .map_err(|e| DecryptError::FormatError(format!("Failed to serialize JSON: {}", e))),
+        ExportFormat::ChromeCsv => {
+            let infos: Vec<LoginInfo> = logins.iter().map(|l| l.info.clone()).collect();
+            Ok(format_chrome_csv(&infos).into_bytes())
+        }
+        ExportFormat::FirefoxCsv => Ok(format_firefox_csv(logins).into_bytes()),
+        ExportFormat::Bitwarden => Ok(format_bitwarden_json(logins, options.folders)?.into_bytes()),
+        ExportFormat::OnePux => format_1pux(logins, Utc::now()),
+        ExportFormat::Kdbx => {
+            let key = options.kdbx_key.clone().unwrap_or_default();
+            format_kdbx(logins, &key, &KdfParams::default())
+        }
+        ExportFormat::Pass => Err(DecryptError::FormatError(
+            "pass exports are written to a directory, use pass_store::export_pass_store"
+                .to_string(),
+        )),
+    }
+}
+
+/// Name of a profile in tables and as the folder or group of formats that
+/// have folders: Chrome profiles keep their bare name
+pub fn profile_folder_name(browser: Browser, profile: &str) -> String {
+    match browser {
+        Browser::Chrome => profile.to_string(),
+        other => format!("{} ({})", profile, other),
+    }
+}
+
+/// Identity of a login for export ids and ordering: its browser and profile
+/// plus Chrome's unique key for a saved password in one store
+pub(crate) fn login_key(login: &LoginInfoWithProfile) -> String {
+    [
+        login.browser.slug(),
+        login.profile.as_str(),
+        login.info.store.as_str(),
+        login.info.signon_realm.as_str(),
+        login.info.origin_url.as_str(),
+        login.info.username_element.as_str(),
+        login.info.username.as_str(),
+        login.info.password_element.as_str(),
+    ]
+    .join("\0")
+}
+
+/// Deterministic UUID-formatted id, so repeated exports produce the same ids
+pub(crate) fn stable_id(seed: &[u8]) -> String {
+    let hash = hex::encode(&Sha256::digest(seed)[..16]);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hash[..8],
+        &hash[8..12],
+        &hash[12..16],
+        &hash[16..20],
+        &hash[20..]
+    )
+}
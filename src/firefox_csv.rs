@@ -0,0 +1,176 @@
+//! Firefox password CSV format
+//!
+//! Columns: `url,username,password,httpRealm,formActionOrigin,guid,
+//! timeCreated,timeLastUsed,timePasswordChanged`, as written by Firefox's
+//! *Export Logins* and read by *Import from a File*. Times are milliseconds
+//! since the Unix epoch; Chrome's WebKit timestamps are converted when the
+//! rows are read (`database::webkit_timestamp_to_datetime`).
+
+use crate::csv::write_record;
+use crate::export::{login_key, stable_id};
+use crate::models::{AuthScheme, LoginInfo, LoginInfoWithProfile};
+use chrono::{DateTime, Utc};
+
+/// Header written by Firefox's password exporter
+pub const FIREFOX_CSV_HEADER: [&str; 9] = [
+    "url",
+    "username",
+    "password",
+    "httpRealm",
+    "formActionOrigin",
+    "guid",
+    "timeCreated",
+    "timeLastUsed",
+    "timePasswordChanged",
+];
+
+/// Format logins as a Firefox password CSV file
+///
+/// Form logins get their action URL's origin as `formActionOrigin`; HTTP
+/// authentication logins get the realm from `signon_realm` as `httpRealm`.
+/// Missing times are left empty, which Firefox fills in on import.
+pub fn format_firefox_csv(logins: &[LoginInfoWithProfile]) -> String {
+    let mut output = String::new();
+    write_record(&mut output, &FIREFOX_CSV_HEADER);
+
+    for login in logins {
+        let info = &login.info;
+        let url = if info.origin_url.is_empty() {
+            &info.url
+        } else {
+            &info.origin_url
+        };
+        let (http_realm, form_action_origin) = if is_http_auth(info.scheme) {
+            (http_realm(info), String::new())
+        } else if info.action_url.is_empty() {
+            (String::new(), String::new())
+        } else {
+            (String::new(), origin(&info.action_url))
+        };
+
+        write_record(
+            &mut output,
+            &[
+                origin(url),
+                info.username.clone(),
+                info.password.clone(),
+                http_realm,
+                form_action_origin,
+                guid(login),
+                millis(info.date_created),
+                millis(info.date_last_used),
+                millis(info.date_password_modified.or(info.date_created)),
+            ],
+        );
+    }
+
+    output
+}
+
+fn is_http_auth(scheme: AuthScheme) -> bool {
+    matches!(
+        scheme,
+        AuthScheme::Basic | AuthScheme::Digest | AuthScheme::Other
+    )
+}
+
+/// Realm of an HTTP auth login: Chrome's `signon_realm` is the origin, a
+/// slash and the realm (`https://example.com/Admin Area`)
+fn http_realm(info: &LoginInfo) -> String {
+    let prefix = format!("{}/", origin(&info.signon_realm));
+    info.signon_realm
+        .strip_prefix(&prefix)
+        .unwrap_or(&info.signon_realm)
+        .to_string()
+}
+
+/// `scheme://host[:port]`, as Firefox stores origins; other URLs (such as
+/// `android://` logins) are kept as they are
+fn origin(url: &str) -> String {
+    match url::Url::parse(url).map(|parsed| parsed.origin()) {
+        Ok(origin) if origin.is_tuple() => origin.ascii_serialization(),
+        _ => url.to_string(),
+    }
+}
+
+/// Braced GUID, stable across exports of the same login; seeded with
+/// Chrome's unique key for a login, so distinct rows get distinct GUIDs
+fn guid(login: &LoginInfoWithProfile) -> String {
+    format!("{{{}}}", stable_id(login_key(login).as_bytes()))
+}
+
+fn millis(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.timestamp_millis().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::Browser;
+    use crate::csv::parse_records;
+    use crate::database::webkit_timestamp_to_datetime;
+
+    #[test]
+    fn test_format_firefox_csv() {
+        let login = |info: LoginInfo| LoginInfoWithProfile {
+            browser: Browser::Chrome,
+            profile: "Default".to_string(),
+            info,
+        };
+        let logins = vec![
+            login(LoginInfo {
+                url: "https://accounts.example.com/login?next=%2F".to_string(),
+                origin_url: "https://accounts.example.com/login?next=%2F".to_string(),
+                action_url: "https://auth.example.com:8443/session".to_string(),
+                signon_realm: "https://accounts.example.com/".to_string(),
+                username: "alice".to_string(),
+                password: "pa,ss".to_string(),
+                // 2024-01-01T00:00:00Z
+                date_created: webkit_timestamp_to_datetime(13_348_540_800_000_000),
+                date_last_used: webkit_timestamp_to_datetime(13_348_540_800_123_456),
+                ..Default::default()
+            }),
+            login(LoginInfo {
+                url: "http://router.local/".to_string(),
+                origin_url: "http://router.local/".to_string(),
+                signon_realm: "http://router.local/Admin Area".to_string(),
+                username: "admin".to_string(),
+                password: "admin".to_string(),
+                scheme: AuthScheme::Basic,
+                ..Default::default()
+            }),
+        ];
+
+        let csv = format_firefox_csv(&logins);
+        let records = parse_records(&csv).unwrap();
+        assert_eq!(records[0], FIREFOX_CSV_HEADER);
+
+        let form = &records[1];
+        assert_eq!(form[0], "https://accounts.example.com");
+        assert_eq!(form[2], "pa,ss");
+        assert_eq!(form[3], "");
+        assert_eq!(form[4], "https://auth.example.com:8443");
+        assert!(form[5].starts_with('{') && form[5].ends_with('}') && form[5].len() == 38);
+        assert_eq!(form[6], "1704067200000");
+        assert_eq!(form[7], "1704067200123");
+        // Never changed: the creation time
+        assert_eq!(form[8], "1704067200000");
+
+        let basic = &records[2];
+        assert_eq!(basic[0], "http://router.local");
+        assert_eq!(basic[3], "Admin Area");
+        assert_eq!(basic[4], "");
+        assert_eq!(basic[6], "");
+        assert_ne!(basic[5], form[5]);
+
+        // Same login, same GUID
+        assert_eq!(format_firefox_csv(&logins), csv);
+
+        // Same site and username in another form: another GUID
+        let mut other_form = logins[0].clone();
+        other_form.info.username_element = "email".to_string();
+        let other = parse_records(&format_firefox_csv(&[other_form])).unwrap();
+        assert_ne!(other[1][5], form[5]);
+    }
+}
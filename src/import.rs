@@ -0,0 +1,394 @@
+//! Import logins into a Chrome `Login Data` database
+//!
+//! Records from a Chrome CSV, JSON or Bitwarden export are inserted into the
+//! `logins` table, encrypted like Chrome encrypts new passwords. Logins that
+//! already exist (same signon realm and username) are left alone.
+
+use crate::bitwarden::parse_bitwarden_json;
+use crate::chrome_csv::parse_chrome_csv;
+use crate::crypto::{DerivedKeys, decrypt_password, encrypt_password};
+use crate::database::{datetime_to_webkit_timestamp, has_table};
+use crate::models::{DecryptError, LoginInfo};
+use chrono::Utc;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+
+/// File Chrome keeps in its user data directory while it is running
+pub const PROFILE_LOCK_FILE: &str = "SingletonLock";
+
+/// Existing passwords tried to make sure the key matches the database
+const KEY_CHECK_SAMPLES: i64 = 5;
+
+/// Supported import formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// Chrome / Google Password Manager CSV (or another CSV with the same columns)
+    ChromeCsv,
+    /// JSON array of logins, as written by `export --format json` and `--json`
+    Json,
+    /// Bitwarden unencrypted JSON
+    Bitwarden,
+}
+
+impl ImportFormat {
+    /// Guess the format of an export from its first character: a JSON array
+    /// of logins, a Bitwarden JSON object or otherwise CSV
+    pub fn detect(input: &str) -> Self {
+        match input.trim_start().chars().next() {
+            Some('[') => ImportFormat::Json,
+            Some('{') => ImportFormat::Bitwarden,
+            _ => ImportFormat::ChromeCsv,
+        }
+    }
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ImportSummary {
+    /// Logins inserted
+    pub imported: usize,
+    /// Logins already in the database
+    pub existing: usize,
+    /// Records without a URL or password
+    pub invalid: usize,
+}
+
+/// Parse an export into logins
+pub fn parse_import(input: &str, format: ImportFormat) -> Result<Vec<LoginInfo>, DecryptError> {
+    match format {
+        ImportFormat::ChromeCsv => parse_chrome_csv(input),
+        ImportFormat::Json => serde_json::from_str(input)
+            .map_err(|e| DecryptError::FormatError(format!("Invalid JSON export: {}", e))),
+        ImportFormat::Bitwarden => Ok(parse_bitwarden_json(input)?
+            .into_iter()
+            .map(|(_, info)| info)
+            .collect()),
+    }
+}
+
+/// Lock file of a running browser that uses `db_path`, if any
+///
+/// Chrome creates `SingletonLock` in the user data directory, the parent of
+/// the profile directory holding `Login Data`.
+pub fn profile_lock(db_path: &Path) -> Option<PathBuf> {
+    db_path
+        .ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(PROFILE_LOCK_FILE))
+        .find(|lock| lock.symlink_metadata().is_ok())
+}
+
+/// Insert `logins` into the `Login Data` database at `db_path`
+///
+/// Refuses to write while the browser holds the profile lock, or when
+/// `keys` cannot decrypt the passwords already stored. All logins are
+/// inserted in one transaction.
+pub fn import_logins(
+    db_path: &Path,
+    keys: &DerivedKeys,
+    logins: &[LoginInfo],
+) -> Result<ImportSummary, DecryptError> {
+    if let Some(lock) = profile_lock(db_path) {
+        return Err(DecryptError::DatabaseError(format!(
+            "The browser is running ({} exists), quit it before importing",
+            lock.display()
+        )));
+    }
+    if !db_path.is_file() {
+        return Err(DecryptError::LoginDataNotFound);
+    }
+
+    let mut conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    check_key(&conn, keys)?;
+    let columns = login_columns(&conn)?;
+    let has_notes = has_table(&conn, "password_notes")?;
+
+    let tx = conn.transaction()?;
+    let mut summary = ImportSummary::default();
+    for info in logins {
+        let Some(values) = login_values(info, keys)? else {
+            summary.invalid += 1;
+            continue;
+        };
+        if login_exists(&tx, &values)? {
+            summary.existing += 1;
+            continue;
+        }
+        let values: Vec<(&str, Value)> = values
+            .into_iter()
+            .filter(|(name, _)| columns.iter().any(|(column, _)| column == name))
+            .collect();
+        let names: Vec<&str> = values.iter().map(|(name, _)| *name).collect();
+        if let Some((column, _)) = columns
+            .iter()
+            .find(|(column, required)| *required && !names.contains(&column.as_str()))
+        {
+            return Err(DecryptError::DatabaseError(format!(
+                "Unsupported Login Data schema: no value for required column '{}'",
+                column
+            )));
+        }
+        let placeholders = vec!["?"; names.len()].join(", ");
+        let sql = format!(
+            "INSERT INTO logins ({}) VALUES ({})",
+            names.join(", "),
+            placeholders
+        );
+        tx.execute(
+            &sql,
+            rusqlite::params_from_iter(values.into_iter().map(|(_, value)| value)),
+        )?;
+        summary.imported += 1;
+
+        if has_notes && !info.note.is_empty() {
+            tx.execute(
+                "INSERT INTO password_notes (parent_id, key, value, date_created)
+                 VALUES (?1, '', ?2, ?3)",
+                rusqlite::params![
+                    tx.last_insert_rowid(),
+                    encrypt_password(keys, &info.note)?,
+                    datetime_to_webkit_timestamp(Some(Utc::now())),
+                ],
+            )?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(summary)
+}
+
+/// Whether a login for the same signon realm and username is stored, the
+/// check Chrome's own CSV import makes
+fn login_exists(conn: &Connection, values: &[(&str, Value)]) -> Result<bool, DecryptError> {
+    let value = |column: &str| {
+        values
+            .iter()
+            .find(|(name, _)| *name == column)
+            .map(|(_, value)| value.clone())
+            .unwrap_or(Value::Null)
+    };
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM logins WHERE signon_realm = ?1 \
+         AND ifnull(username_value, '') = ?2)",
+        rusqlite::params![value("signon_realm"), value("username_value")],
+        |row| row.get(0),
+    )?)
+}
+
+/// Fail if the database has passwords encrypted with the version new
+/// passwords get, and none of them decrypts with `keys`
+fn check_key(conn: &Connection, keys: &DerivedKeys) -> Result<(), DecryptError> {
+    let mut stmt = conn.prepare(
+        "SELECT password_value FROM logins \
+         WHERE substr(password_value, 1, 3) = CAST(?1 AS BLOB) LIMIT ?2",
+    )?;
+    let samples = stmt
+        .query_map(
+            rusqlite::params![keys.current_version(), KEY_CHECK_SAMPLES],
+            |row| row.get::<_, Vec<u8>>(0),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !samples.is_empty()
+        && !samples
+            .iter()
+            .any(|blob| decrypt_password(keys, blob).is_ok())
+    {
+        return Err(DecryptError::CryptoError(
+            "The key does not decrypt the passwords already in the database".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Columns of the `logins` table, with whether a value is required (NOT
+/// NULL without a default)
+fn login_columns(conn: &Connection) -> Result<Vec<(String, bool)>, DecryptError> {
+    let mut stmt = conn.prepare(
+        "SELECT name, \"notnull\" AND dflt_value IS NULL AND NOT pk \
+         FROM pragma_table_info('logins')",
+    )?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.is_empty() {
+        return Err(DecryptError::DatabaseError("No logins table".to_string()));
+    }
+    Ok(columns)
+}
+
+/// Column values of a new `logins` row, or `None` for an unusable record
+///
+/// The signon realm defaults to the origin (`https://example.com/`), like
+/// Chrome's own CSV import; missing times default to now.
+fn login_values(
+    info: &LoginInfo,
+    keys: &DerivedKeys,
+) -> Result<Option<Vec<(&'static str, Value)>>, DecryptError> {
+    let origin_url = if info.origin_url.is_empty() {
+        info.url.clone()
+    } else {
+        info.origin_url.clone()
+    };
+    let signon_realm = if info.signon_realm.is_empty() {
+        signon_realm(&origin_url)
+    } else {
+        info.signon_realm.clone()
+    };
+    let usable = !origin_url.is_empty() && !info.password.is_empty();
+
+    let created = info.date_created.unwrap_or_else(Utc::now);
+    let text = |value: &str| Value::Text(value.to_string());
+    let int = Value::Integer;
+    let values = vec![
+        ("origin_url", text(&origin_url)),
+        ("action_url", text(&info.action_url)),
+        ("username_element", text(&info.username_element)),
+        ("username_value", text(&info.username)),
+        ("password_element", text(&info.password_element)),
+        (
+            "password_value",
+            Value::Blob(encrypt_password(keys, &info.password)?),
+        ),
+        ("submit_element", text("")),
+        ("signon_realm", text(&signon_realm)),
+        (
+            "date_created",
+            int(datetime_to_webkit_timestamp(Some(created))),
+        ),
+        ("blacklisted_by_user", int(0)),
+        ("scheme", int(info.scheme.to_db())),
+        ("password_type", int(0)),
+        ("times_used", int(info.times_used)),
+        ("display_name", text("")),
+        ("icon_url", text("")),
+        ("federation_url", text("")),
+        ("skip_zero_click", int(0)),
+        ("generation_upload_status", int(0)),
+        (
+            "date_last_used",
+            int(datetime_to_webkit_timestamp(info.date_last_used)),
+        ),
+        (
+            "date_password_modified",
+            int(datetime_to_webkit_timestamp(Some(
+                info.date_password_modified.unwrap_or(created),
+            ))),
+        ),
+        ("sharing_notification_displayed", int(0)),
+    ];
+
+    Ok(usable.then_some(values))
+}
+
+/// `scheme://host[:port]/` for web URLs; other URLs (such as `android://`
+/// logins) are used as they are
+pub(crate) fn signon_realm(url: &str) -> String {
+    match url::Url::parse(url).map(|parsed| parsed.origin()) {
+        Ok(origin) if origin.is_tuple() => format!("{}/", origin.ascii_serialization()),
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{SnapshotMode, query_login_data};
+    use crate::models::{AuthScheme, QueryParams};
+    use crate::test_support::{TestLogin, create_login_db};
+    use chrono::TimeZone;
+
+    fn read_back(db_path: &Path, keys: &DerivedKeys) -> Vec<LoginInfo> {
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: keys,
+            snapshot: SnapshotMode::Backup,
+        };
+        query_login_data(db_path, &params).unwrap()
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = dir.path().join("Default");
+        std::fs::create_dir(&profile).unwrap();
+        let db_path = profile.join("Login Data");
+        let keys = DerivedKeys::for_linux(Some("secret"));
+        create_login_db(
+            &db_path,
+            &keys,
+            &[TestLogin::new("https://github.com/", "alice", "old").version("v11")],
+        );
+
+        let created = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let csv = "name,url,username,password,note\n\
+                   github.com,https://github.com/,alice,new,\n\
+                   example.com,https://www.example.com/login,bob,\"p,a\"\"ss\",pin: 1234\n\
+                   broken,,carol,pw,\n";
+        let mut logins = parse_import(csv, ImportFormat::ChromeCsv).unwrap();
+        logins[1].date_created = Some(created);
+        logins.push(LoginInfo {
+            url: "https://router.local/".to_string(),
+            signon_realm: "https://router.local/Admin".to_string(),
+            username: "admin".to_string(),
+            password: "admin".to_string(),
+            scheme: AuthScheme::Basic,
+            ..Default::default()
+        });
+
+        let summary = import_logins(&db_path, &keys, &logins).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                imported: 2,
+                existing: 1,
+                invalid: 1
+            }
+        );
+
+        let stored = read_back(&db_path, &keys);
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[0].password, "old");
+        let example = &stored[1];
+        assert_eq!(example.url, "https://www.example.com/login");
+        assert_eq!(example.signon_realm, "https://www.example.com/");
+        assert_eq!(example.password, "p,a\"ss");
+        assert_eq!(example.note, "pin: 1234");
+        assert_eq!(example.date_created, Some(created));
+        assert_eq!(example.date_password_modified, Some(created));
+        assert_eq!(stored[2].scheme, AuthScheme::Basic);
+        assert_eq!(stored[2].signon_realm, "https://router.local/Admin");
+
+        // A different key would write passwords the browser cannot read
+        let wrong = DerivedKeys::for_linux(Some("other"));
+        assert!(import_logins(&db_path, &wrong, &logins).is_err());
+    }
+
+    #[test]
+    fn test_import_refuses_locked_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = dir.path().join("Profile 1");
+        std::fs::create_dir(&profile).unwrap();
+        let db_path = profile.join("Login Data");
+        let keys = DerivedKeys::for_linux(None);
+        create_login_db(&db_path, &keys, &[]);
+        std::os::unix::fs::symlink("host-12345", dir.path().join(PROFILE_LOCK_FILE)).unwrap();
+
+        let logins = parse_import(
+            r#"[{"url": "https://example.com/", "username": "a", "password": "b"}]"#,
+            ImportFormat::Json,
+        )
+        .unwrap();
+        assert_eq!(
+            profile_lock(&db_path),
+            Some(dir.path().join(PROFILE_LOCK_FILE))
+        );
+        assert!(import_logins(&db_path, &keys, &logins).is_err());
+        assert!(read_back(&db_path, &keys).is_empty());
+
+        std::fs::remove_file(dir.path().join(PROFILE_LOCK_FILE)).unwrap();
+        assert_eq!(import_logins(&db_path, &keys, &logins).unwrap().imported, 1);
+    }
+}
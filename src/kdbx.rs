@@ -0,0 +1,618 @@
+//! KeePass KDBX 4 export
+//!
+//! Writes an encrypted database readable by KeePass 2.35+ and KeePassXC:
+//! Argon2d key derivation, AES-256-CBC payload encryption, HMAC-SHA256
+//! authenticated blocks and ChaCha20-protected password fields. The XML
+//! payload only ever exists in memory.
+
+use crate::chrome_csv::display_name;
+use crate::export::profile_folder_name;
+use crate::models::{DecryptError, LoginInfo, LoginInfoWithProfile};
+use aes::Aes256;
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockEncryptMut, KeyIvInit};
+use chacha20::ChaCha20;
+use chacha20::cipher::StreamCipher;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+
+/// File signatures and format version 4.0
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+const VERSION_4_0: u32 = 0x0004_0000;
+
+/// Outer header field ids
+const HEADER_END: u8 = 0;
+const HEADER_CIPHER_ID: u8 = 2;
+const HEADER_COMPRESSION: u8 = 3;
+const HEADER_MASTER_SEED: u8 = 4;
+const HEADER_ENCRYPTION_IV: u8 = 7;
+const HEADER_KDF_PARAMETERS: u8 = 11;
+
+/// Inner header field ids
+const INNER_HEADER_END: u8 = 0;
+const INNER_RANDOM_STREAM_ID: u8 = 1;
+const INNER_RANDOM_STREAM_KEY: u8 = 2;
+
+/// Inner random stream algorithm: ChaCha20
+const INNER_STREAM_CHACHA20: u32 = 3;
+
+/// Cipher UUID of AES-256-CBC
+const CIPHER_AES256: [u8; 16] = [
+    0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50, 0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff,
+];
+
+/// KDF UUID of Argon2d
+const KDF_ARGON2D: [u8; 16] = [
+    0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b, 0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c,
+];
+
+/// VariantDictionary format version and value types
+const VARIANT_DICTIONARY_VERSION: u16 = 0x0100;
+const VARIANT_UINT32: u8 = 0x04;
+const VARIANT_UINT64: u8 = 0x05;
+const VARIANT_BYTES: u8 = 0x42;
+
+/// Payload bytes per HMAC block
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Seconds between 0001-01-01 (KDBX 4 time epoch) and 1970-01-01
+const KDBX_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Argon2d cost parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory in bytes
+    pub memory: u64,
+    pub iterations: u64,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// KeePassXC's defaults: 64 MiB, parallelism 2
+    fn default() -> Self {
+        Self {
+            memory: 64 * 1024 * 1024,
+            iterations: 10,
+            parallelism: 2,
+        }
+    }
+}
+
+/// Credentials protecting the database: a master password, a key file, or both
+#[derive(Debug, Clone, Default)]
+pub struct KdbxKey {
+    pub password: Option<String>,
+    /// Contents of a key file
+    pub key_file: Option<Vec<u8>>,
+}
+
+impl KdbxKey {
+    /// Composite key: SHA-256 over the hashed password and the key file key
+    fn composite(&self) -> Result<[u8; 32], DecryptError> {
+        if self.password.is_none() && self.key_file.is_none() {
+            return Err(DecryptError::CryptoError(
+                "KDBX export needs a master password or a key file".to_string(),
+            ));
+        }
+
+        let mut hasher = Sha256::new();
+        if let Some(password) = &self.password {
+            hasher.update(Sha256::digest(password.as_bytes()));
+        }
+        if let Some(key_file) = &self.key_file {
+            hasher.update(key_file_key(key_file)?);
+        }
+        Ok(hasher.finalize().into())
+    }
+}
+
+/// Key stored in a key file, in any format KeePass accepts: XML (version
+/// 1.0 or 2.0), 32 raw bytes, 64 hex digits, or any other file (hashed)
+pub fn key_file_key(contents: &[u8]) -> Result<[u8; 32], DecryptError> {
+    if let Ok(text) = std::str::from_utf8(contents)
+        && text.contains("<KeyFile>")
+    {
+        return xml_key_file_key(text);
+    }
+    if contents.len() == 32 {
+        return Ok(contents.try_into().unwrap());
+    }
+    if contents.len() == 64
+        && let Ok(key) = hex::decode(contents)
+    {
+        return Ok(key.try_into().unwrap());
+    }
+    Ok(Sha256::digest(contents).into())
+}
+
+fn xml_key_file_key(text: &str) -> Result<[u8; 32], DecryptError> {
+    let invalid = |reason: &str| DecryptError::FormatError(format!("Invalid key file: {}", reason));
+    let version = element_text(text, "Version").ok_or_else(|| invalid("no version"))?;
+    let data_start = text.find("<Data").ok_or_else(|| invalid("no key data"))?;
+    let data_tag_end = data_start
+        + text[data_start..]
+            .find('>')
+            .ok_or_else(|| invalid("no key data"))?;
+    let data = element_text(&text[data_start..], "Data").ok_or_else(|| invalid("no key data"))?;
+
+    let key = if version.trim().starts_with("2.") {
+        let digits: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+        let key = hex::decode(digits).map_err(|_| invalid("key data is not hex"))?;
+        let tag = &text[data_start..data_tag_end];
+        if let Some(hash) = tag
+            .split("Hash=\"")
+            .nth(1)
+            .and_then(|s| s.split('"').next())
+        {
+            let expected = hex::encode_upper(&Sha256::digest(&key)[..4]);
+            if !hash.eq_ignore_ascii_case(&expected) {
+                return Err(invalid("key data does not match its hash"));
+            }
+        }
+        key
+    } else {
+        BASE64
+            .decode(data.trim())
+            .map_err(|_| invalid("key data is not base64"))?
+    };
+
+    key.try_into().map_err(|_| invalid("key is not 32 bytes"))
+}
+
+/// Text between `<name ...>` and `</name>`
+fn element_text<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let start = text.find(&format!("<{}", name))?;
+    let content_start = start + text[start..].find('>')? + 1;
+    let content_end = content_start + text[content_start..].find(&format!("</{}>", name))?;
+    Some(&text[content_start..content_end])
+}
+
+/// A new random key file in KeePass's XML 2.0 format
+pub fn generate_key_file() -> Vec<u8> {
+    let key = random_bytes::<32>();
+    let hash = hex::encode_upper(&Sha256::digest(key)[..4]);
+    let groups: Vec<String> = key.chunks(4).map(hex::encode_upper).collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <KeyFile>\n\
+         \t<Meta>\n\
+         \t\t<Version>2.0</Version>\n\
+         \t</Meta>\n\
+         \t<Key>\n\
+         \t\t<Data Hash=\"{}\">\n\
+         \t\t\t{}\n\
+         \t\t\t{}\n\
+         \t\t</Data>\n\
+         \t</Key>\n\
+         </KeyFile>\n",
+        hash,
+        groups[..4].join(" "),
+        groups[4..].join(" ")
+    )
+    .into_bytes()
+}
+
+/// Write logins as a KDBX 4 database with one group per browser profile
+pub fn format_kdbx(
+    logins: &[LoginInfoWithProfile],
+    key: &KdbxKey,
+    kdf: &KdfParams,
+) -> Result<Vec<u8>, DecryptError> {
+    let composite = key.composite()?;
+    let master_seed = random_bytes::<32>();
+    let kdf_salt = random_bytes::<32>();
+    let iv = random_bytes::<16>();
+    let stream_key = random_bytes::<64>();
+
+    let header = outer_header(&master_seed, &iv, &kdf_salt, kdf);
+    let transformed = argon2d(&composite, &kdf_salt, kdf)?;
+    let (cipher_key, hmac_key) = derive_keys(&master_seed, &transformed);
+
+    let mut payload = inner_header(&stream_key);
+    payload.extend(database_xml(logins, &stream_key, Utc::now()).into_bytes());
+    let encrypted = cbc::Encryptor::<Aes256>::new(&cipher_key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(&payload);
+
+    let mut output = header.clone();
+    output.extend(Sha256::digest(&header));
+    output.extend(hmac(&block_key(&hmac_key, u64::MAX), &[&header]));
+    for (index, block) in encrypted
+        .chunks(BLOCK_SIZE)
+        .chain(std::iter::once(&[][..]))
+        .enumerate()
+    {
+        let index = index as u64;
+        let size = (block.len() as u32).to_le_bytes();
+        output.extend(hmac(
+            &block_key(&hmac_key, index),
+            &[&index.to_le_bytes(), &size, block],
+        ));
+        output.extend(size);
+        output.extend(block);
+    }
+
+    Ok(output)
+}
+
+fn outer_header(master_seed: &[u8], iv: &[u8], kdf_salt: &[u8], kdf: &KdfParams) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend(SIGNATURE_1.to_le_bytes());
+    header.extend(SIGNATURE_2.to_le_bytes());
+    header.extend(VERSION_4_0.to_le_bytes());
+
+    let mut kdf_parameters = VARIANT_DICTIONARY_VERSION.to_le_bytes().to_vec();
+    let mut variant = |value_type: u8, name: &str, value: &[u8]| {
+        kdf_parameters.push(value_type);
+        kdf_parameters.extend((name.len() as u32).to_le_bytes());
+        kdf_parameters.extend(name.as_bytes());
+        kdf_parameters.extend((value.len() as u32).to_le_bytes());
+        kdf_parameters.extend(value);
+    };
+    variant(VARIANT_BYTES, "$UUID", &KDF_ARGON2D);
+    variant(VARIANT_BYTES, "S", kdf_salt);
+    variant(VARIANT_UINT32, "P", &kdf.parallelism.to_le_bytes());
+    variant(VARIANT_UINT64, "M", &kdf.memory.to_le_bytes());
+    variant(VARIANT_UINT64, "I", &kdf.iterations.to_le_bytes());
+    variant(VARIANT_UINT32, "V", &0x13u32.to_le_bytes());
+    kdf_parameters.push(0);
+
+    let fields: [(u8, &[u8]); 6] = [
+        (HEADER_CIPHER_ID, &CIPHER_AES256),
+        (HEADER_COMPRESSION, &0u32.to_le_bytes()),
+        (HEADER_MASTER_SEED, master_seed),
+        (HEADER_ENCRYPTION_IV, iv),
+        (HEADER_KDF_PARAMETERS, &kdf_parameters),
+        (HEADER_END, b"\r\n\r\n"),
+    ];
+    for (id, data) in fields {
+        header.push(id);
+        header.extend((data.len() as u32).to_le_bytes());
+        header.extend(data);
+    }
+    header
+}
+
+fn inner_header(stream_key: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    let fields: [(u8, &[u8]); 3] = [
+        (INNER_RANDOM_STREAM_ID, &INNER_STREAM_CHACHA20.to_le_bytes()),
+        (INNER_RANDOM_STREAM_KEY, stream_key),
+        (INNER_HEADER_END, &[]),
+    ];
+    for (id, data) in fields {
+        header.push(id);
+        header.extend((data.len() as u32).to_le_bytes());
+        header.extend(data);
+    }
+    header
+}
+
+fn argon2d(composite: &[u8], salt: &[u8], kdf: &KdfParams) -> Result<[u8; 32], DecryptError> {
+    let params = Params::new(
+        (kdf.memory / 1024) as u32,
+        kdf.iterations as u32,
+        kdf.parallelism,
+        Some(32),
+    )
+    .map_err(|e| DecryptError::CryptoError(format!("Invalid Argon2 parameters: {}", e)))?;
+    let mut transformed = [0u8; 32];
+    Argon2::new(Algorithm::Argon2d, Version::V0x13, params)
+        .hash_password_into(composite, salt, &mut transformed)
+        .map_err(|e| DecryptError::CryptoError(format!("Argon2 failed: {}", e)))?;
+    Ok(transformed)
+}
+
+/// Payload encryption key and HMAC base key
+fn derive_keys(master_seed: &[u8], transformed: &[u8]) -> ([u8; 32], [u8; 64]) {
+    let cipher_key = Sha256::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .finalize();
+    let hmac_key = Sha512::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .chain_update([1u8])
+        .finalize();
+    (cipher_key.into(), hmac_key.into())
+}
+
+/// HMAC key of one block (`u64::MAX` for the header)
+fn block_key(hmac_key: &[u8; 64], index: u64) -> [u8; 64] {
+    Sha512::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(hmac_key)
+        .finalize()
+        .into()
+}
+
+fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Inner random stream protecting password values
+fn inner_stream(stream_key: &[u8]) -> ChaCha20 {
+    let hash = Sha512::digest(stream_key);
+    ChaCha20::new(hash[..32].into(), hash[32..44].into())
+}
+
+fn database_xml(logins: &[LoginInfoWithProfile], stream_key: &[u8], now: DateTime<Utc>) -> String {
+    let mut stream = inner_stream(stream_key);
+    let mut groups: Vec<(String, Vec<&LoginInfo>)> = Vec::new();
+    for login in logins {
+        let name = profile_folder_name(login.browser, &login.profile);
+        match groups.iter_mut().find(|(group, _)| *group == name) {
+            Some((_, entries)) => entries.push(&login.info),
+            None => groups.push((name, vec![&login.info])),
+        }
+    }
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>\n",
+    );
+    xml.push_str(&format!(
+        "<Meta><Generator>chrome-password-unlock</Generator>\
+         <DatabaseName>Browser passwords</DatabaseName>\
+         <DatabaseNameChanged>{now}</DatabaseNameChanged>\
+         <MemoryProtection><ProtectTitle>False</ProtectTitle>\
+         <ProtectUserName>False</ProtectUserName><ProtectPassword>True</ProtectPassword>\
+         <ProtectURL>False</ProtectURL><ProtectNotes>False</ProtectNotes></MemoryProtection>\
+         <RecycleBinEnabled>False</RecycleBinEnabled></Meta>\n",
+        now = kdbx_time(now)
+    ));
+    xml.push_str("<Root>\n");
+    open_group(&mut xml, "Browser passwords", now);
+    for (name, entries) in groups {
+        open_group(&mut xml, &name, now);
+        for info in entries {
+            write_entry(&mut xml, info, &mut stream, now);
+        }
+        xml.push_str("</Group>\n");
+    }
+    xml.push_str("</Group>\n<DeletedObjects/>\n</Root>\n</KeePassFile>\n");
+    xml
+}
+
+fn open_group(xml: &mut String, name: &str, now: DateTime<Utc>) {
+    xml.push_str(&format!(
+        "<Group><UUID>{}</UUID><Name>{}</Name>{}<IsExpanded>True</IsExpanded>\n",
+        BASE64.encode(random_bytes::<16>()),
+        xml_escape(name),
+        times_xml(now, now, now, 0)
+    ));
+}
+
+fn write_entry(xml: &mut String, info: &LoginInfo, stream: &mut ChaCha20, now: DateTime<Utc>) {
+    let created = info.date_created.unwrap_or(now);
+    let modified = info.date_password_modified.unwrap_or(created);
+    let accessed = info.date_last_used.unwrap_or(modified);
+
+    xml.push_str(&format!(
+        "<Entry><UUID>{}</UUID>{}",
+        BASE64.encode(random_bytes::<16>()),
+        times_xml(created, modified, accessed, info.times_used)
+    ));
+    for (key, value) in [
+        ("Title", display_name(&info.url)),
+        ("UserName", info.username.clone()),
+        ("URL", info.url.clone()),
+        ("Notes", info.note.clone()),
+    ] {
+        xml.push_str(&format!(
+            "<String><Key>{}</Key><Value>{}</Value></String>",
+            key,
+            xml_escape(&value)
+        ));
+    }
+
+    let mut password = info.password.as_bytes().to_vec();
+    stream.apply_keystream(&mut password);
+    xml.push_str(&format!(
+        "<String><Key>Password</Key><Value Protected=\"True\">{}</Value></String></Entry>\n",
+        BASE64.encode(password)
+    ));
+}
+
+fn times_xml(
+    created: DateTime<Utc>,
+    modified: DateTime<Utc>,
+    accessed: DateTime<Utc>,
+    usage_count: i64,
+) -> String {
+    format!(
+        "<Times><CreationTime>{}</CreationTime><LastModificationTime>{}</LastModificationTime>\
+         <LastAccessTime>{}</LastAccessTime><ExpiryTime>{}</ExpiryTime><Expires>False</Expires>\
+         <UsageCount>{}</UsageCount><LocationChanged>{}</LocationChanged></Times>",
+        kdbx_time(created),
+        kdbx_time(modified),
+        kdbx_time(accessed),
+        kdbx_time(created),
+        usage_count.max(0),
+        kdbx_time(modified),
+    )
+}
+
+/// KDBX 4 timestamp: base64 of little-endian seconds since 0001-01-01
+fn kdbx_time(datetime: DateTime<Utc>) -> String {
+    BASE64.encode((datetime.timestamp() + KDBX_EPOCH_OFFSET).to_le_bytes())
+}
+
+/// Escape text for XML, dropping characters XML 1.0 cannot represent
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::Browser;
+    use crate::test_support::TestProfileLogin;
+    use chrono::TimeZone;
+    use keepass::{Database, DatabaseKey};
+
+    /// Cheap parameters so the tests run quickly
+    const TEST_KDF: KdfParams = KdfParams {
+        memory: 1024 * 1024,
+        iterations: 2,
+        parallelism: 1,
+    };
+
+    /// Open an export with the `keepass` crate, a KDBX reader that shares
+    /// no code with the writer
+    fn read_kdbx(data: &[u8], key: &KdbxKey) -> Result<Database, String> {
+        let mut database_key = DatabaseKey::new();
+        if let Some(password) = &key.password {
+            database_key = database_key.with_password(password);
+        }
+        if let Some(key_file) = &key.key_file {
+            database_key = database_key.with_keyfile(&mut key_file.as_slice()).unwrap();
+        }
+        Database::parse(data, database_key).map_err(|e| e.to_string())
+    }
+
+    fn logins() -> Vec<LoginInfoWithProfile> {
+        let login = |url: &str, password: &str| {
+            TestProfileLogin::new(url, "alice", password)
+                .note("pin <1234> & more")
+                .date_created(Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()))
+        };
+        vec![
+            login("https://www.example.com/", "hunter2").build(),
+            login("https://b.example/", "p<a>ss\"word")
+                .browser(Browser::Brave)
+                .build(),
+            login("https://c.example/", "third").build(),
+        ]
+    }
+
+    #[test]
+    fn test_kdbx_round_trip_with_password() {
+        let key = KdbxKey {
+            password: Some("correct horse".to_string()),
+            key_file: None,
+        };
+        let data = format_kdbx(&logins(), &key, &TEST_KDF).unwrap();
+        let database = read_kdbx(&data, &key).unwrap();
+
+        let root = database.root();
+        let mut groups: Vec<_> = root.groups().map(|group| group.name.clone()).collect();
+        groups.sort();
+        assert_eq!(groups, ["Default", "Default (Brave)"]);
+        let chrome = root.group_by_name("Default").unwrap();
+        let brave = root.group_by_name("Default (Brave)").unwrap();
+        assert_eq!(chrome.entries().count(), 2);
+
+        let entry = brave.entries().next().unwrap();
+        assert_eq!(entry.get_title(), Some("b.example"));
+        assert_eq!(entry.get_url(), Some("https://b.example/"));
+        assert_eq!(entry.get_username(), Some("alice"));
+        assert_eq!(entry.get_password(), Some("p<a>ss\"word"));
+        assert_eq!(entry.get("Notes"), Some("pin <1234> & more"));
+        assert_eq!(
+            entry.times.creation,
+            Some(
+                Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0)
+                    .unwrap()
+                    .naive_utc()
+            )
+        );
+
+        let mut passwords: Vec<_> = chrome
+            .entries()
+            .map(|entry| {
+                (
+                    entry.get_title().unwrap().to_string(),
+                    entry.get_password().unwrap().to_string(),
+                )
+            })
+            .collect();
+        passwords.sort();
+        assert_eq!(
+            passwords,
+            [
+                ("c.example".to_string(), "third".to_string()),
+                ("example.com".to_string(), "hunter2".to_string()),
+            ]
+        );
+        // Passwords never appear in the file itself
+        assert!(!data.windows(7).any(|w| w == b"hunter2"));
+
+        let wrong = KdbxKey {
+            password: Some("wrong".to_string()),
+            key_file: None,
+        };
+        assert!(read_kdbx(&data, &wrong).is_err());
+    }
+
+    #[test]
+    fn test_kdbx_with_key_file() {
+        let key_file = generate_key_file();
+        let key = KdbxKey {
+            password: None,
+            key_file: Some(key_file.clone()),
+        };
+        let data = format_kdbx(&logins(), &key, &TEST_KDF).unwrap();
+        assert!(read_kdbx(&data, &key).is_ok());
+
+        let with_password = KdbxKey {
+            password: Some(String::new()),
+            key_file: Some(key_file),
+        };
+        assert!(read_kdbx(&data, &with_password).is_err());
+        assert!(format_kdbx(&logins(), &KdbxKey::default(), &TEST_KDF).is_err());
+    }
+
+    #[test]
+    fn test_key_file_formats() {
+        let key = [7u8; 32];
+        assert_eq!(key_file_key(&key).unwrap(), key);
+        assert_eq!(key_file_key(hex::encode(key).as_bytes()).unwrap(), key);
+        assert_eq!(
+            key_file_key(b"any other file").unwrap(),
+            <[u8; 32]>::from(Sha256::digest(b"any other file"))
+        );
+
+        let v1 = format!(
+            "<?xml version=\"1.0\"?><KeyFile><Meta><Version>1.00</Version></Meta>\
+             <Key><Data>{}</Data></Key></KeyFile>",
+            BASE64.encode(key)
+        );
+        assert_eq!(key_file_key(v1.as_bytes()).unwrap(), key);
+
+        let v2 = String::from_utf8(generate_key_file()).unwrap();
+        assert!(key_file_key(v2.as_bytes()).is_ok());
+        let tampered = v2.replacen("<Data Hash=\"", "<Data Hash=\"0", 1);
+        assert!(key_file_key(tampered.as_bytes()).is_err());
+    }
+}
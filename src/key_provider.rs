@@ -0,0 +1,402 @@
+use crate::browser::Browser;
+use crate::cache::MasterPasswordCache;
+use crate::crypto::{DerivedKeys, decrypt_password, version_prefix};
+use crate::keychain::get_master_password_with_cache;
+use crate::models::DecryptError;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Source of the AES keys used to decrypt a profile
+///
+/// Implementations fetch a secret from somewhere (keychain, environment,
+/// file, ...) and derive the per-version keys from it.
+pub trait KeyProvider {
+    /// Short human-readable name, used in logs
+    fn name(&self) -> &str;
+
+    /// Fetch the secret and derive the decryption keys
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError>;
+}
+
+/// How a secret is turned into keys
+///
+/// Profiles copied from another machine must be decrypted with the scheme of
+/// the platform that encrypted them, not the one running this tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyScheme {
+    /// 1003 PBKDF2 iterations, every blob is "v10"
+    Macos,
+    /// 1 PBKDF2 iteration, "v10" uses "peanuts", "v11" uses the secret
+    Linux,
+}
+
+impl KeyScheme {
+    /// Scheme of the current platform
+    pub fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            KeyScheme::Macos
+        } else {
+            KeyScheme::Linux
+        }
+    }
+
+    /// Derive the keys for a secret
+    pub fn derive(self, secret: &str) -> DerivedKeys {
+        match self {
+            KeyScheme::Macos => DerivedKeys::for_macos(secret),
+            KeyScheme::Linux => DerivedKeys::for_linux(Some(secret)),
+        }
+    }
+}
+
+/// Cache settings shared by the system key store providers
+///
+/// `samples` are encrypted password blobs from the profiles to be decrypted;
+/// a cached secret that decrypts none of them is treated as stale.
+#[derive(Default)]
+pub struct SystemStoreOptions {
+    pub cache: Option<MasterPasswordCache>,
+    pub samples: Vec<Vec<u8>>,
+}
+
+impl SystemStoreOptions {
+    fn master_password(&self, browser: Browser, scheme: KeyScheme) -> Result<String, DecryptError> {
+        // Only blobs encrypted with the secret say anything about it
+        let samples: Vec<&[u8]> = self
+            .samples
+            .iter()
+            .filter(|blob| scheme != KeyScheme::Linux || version_prefix(blob) == Some("v11"))
+            .map(Vec::as_slice)
+            .collect();
+        let is_valid = |secret: &str| {
+            let keys = scheme.derive(secret);
+            samples.is_empty()
+                || samples
+                    .iter()
+                    .any(|blob| decrypt_password(&keys, blob).is_ok())
+        };
+
+        get_master_password_with_cache(browser, self.cache.as_ref(), &is_valid)
+    }
+}
+
+/// macOS Keychain ("Chrome Safe Storage", "Brave Safe Storage", ...)
+#[cfg(target_os = "macos")]
+#[derive(Default)]
+pub struct KeychainProvider {
+    pub browser: Browser,
+    pub options: SystemStoreOptions,
+}
+
+#[cfg(target_os = "macos")]
+impl KeyProvider for KeychainProvider {
+    fn name(&self) -> &str {
+        "macOS Keychain"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        let master_password = self
+            .options
+            .master_password(self.browser, KeyScheme::Macos)?;
+        Ok(DerivedKeys::for_macos(&master_password))
+    }
+}
+
+/// Linux freedesktop Secret Service ("Chrome Safe Storage", ...)
+///
+/// The secret is only needed for "v11" blobs, so a missing keyring is not
+/// fatal: "v10" blobs can still be decrypted.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+pub struct SecretServiceProvider {
+    pub browser: Browser,
+    pub options: SystemStoreOptions,
+}
+
+#[cfg(target_os = "linux")]
+impl KeyProvider for SecretServiceProvider {
+    fn name(&self) -> &str {
+        "Secret Service"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        let secret = match self.options.master_password(self.browser, KeyScheme::Linux) {
+            Ok(secret) => Some(secret),
+            Err(e) => {
+                tracing::warn!(
+                    "No Secret Service key, only v10 passwords can be decrypted: {}",
+                    e
+                );
+                None
+            }
+        };
+        Ok(DerivedKeys::for_linux(secret.as_deref()))
+    }
+}
+
+/// KDE Wallet ("Chrome Keys" / "Chrome Safe Storage", ...)
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct KWalletProvider {
+    pub browser: Browser,
+    pub wallet: String,
+}
+
+#[cfg(target_os = "linux")]
+impl Default for KWalletProvider {
+    fn default() -> Self {
+        Self {
+            browser: Browser::default(),
+            wallet: "kdewallet".to_string(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl KeyProvider for KWalletProvider {
+    fn name(&self) -> &str {
+        "KWallet"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        let secret = crate::keychain::get_kwallet_master_password(self.browser, &self.wallet)?;
+        Ok(DerivedKeys::for_linux(Some(&secret)))
+    }
+}
+
+/// Secret read from an environment variable
+#[derive(Debug)]
+pub struct EnvKeyProvider {
+    pub var: String,
+    pub scheme: KeyScheme,
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn name(&self) -> &str {
+        "environment variable"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        let secret = std::env::var(&self.var).map_err(|e| {
+            DecryptError::KeychainError(format!("Cannot read ${}: {}", self.var, e))
+        })?;
+        Ok(self.scheme.derive(&secret))
+    }
+}
+
+/// Secret read from a file (a trailing newline is ignored)
+#[derive(Debug)]
+pub struct FileKeyProvider {
+    pub path: PathBuf,
+    pub scheme: KeyScheme,
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn name(&self) -> &str {
+        "key file"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        let secret = std::fs::read_to_string(&self.path).map_err(|e| {
+            DecryptError::IoError(format!("Cannot read {}: {}", self.path.display(), e))
+        })?;
+        Ok(self.scheme.derive(trim_line_ending(&secret)))
+    }
+}
+
+/// Secret read from the first line of standard input
+#[derive(Debug)]
+pub struct StdinKeyProvider {
+    pub scheme: KeyScheme,
+}
+
+impl KeyProvider for StdinKeyProvider {
+    fn name(&self) -> &str {
+        "stdin"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        let mut secret = String::new();
+        std::io::stdin().read_line(&mut secret)?;
+        Ok(self.scheme.derive(trim_line_ending(&secret)))
+    }
+}
+
+/// Fixed, already-known keys (tests, scripted pipelines)
+#[derive(Debug, Clone)]
+pub struct FixedKeyProvider {
+    keys: DerivedKeys,
+}
+
+impl FixedKeyProvider {
+    /// Use the given keys as-is
+    pub fn new(keys: DerivedKeys) -> Self {
+        Self { keys }
+    }
+
+    /// Derive the keys from a known secret
+    pub fn from_password(secret: &str, scheme: KeyScheme) -> Self {
+        Self::new(scheme.derive(secret))
+    }
+}
+
+impl KeyProvider for FixedKeyProvider {
+    fn name(&self) -> &str {
+        "fixed key"
+    }
+
+    fn derived_keys(&self) -> Result<DerivedKeys, DecryptError> {
+        Ok(self.keys.clone())
+    }
+}
+
+/// Key provider of the current platform for a browser
+pub fn default_provider(browser: Browser) -> Box<dyn KeyProvider> {
+    system_store_provider(browser, SystemStoreOptions::default())
+}
+
+/// Key store provider of the current platform with cache settings
+pub fn system_store_provider(
+    browser: Browser,
+    options: SystemStoreOptions,
+) -> Box<dyn KeyProvider> {
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(KeychainProvider { browser, options })
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(SecretServiceProvider { browser, options })
+    }
+}
+
+/// Command-line description of a key provider
+///
+/// Accepted forms:
+/// - `keychain` (macOS), `secret-service`, `kwallet[:<wallet>]` (Linux)
+/// - `env:<VAR>`, `file:<path>`, `stdin`
+///
+/// There is deliberately no form taking the secret itself, which would leave
+/// it in the shell history and the process list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    Keychain,
+    SecretService,
+    KWallet(String),
+    Env(String),
+    File(PathBuf),
+    Stdin,
+}
+
+impl KeySource {
+    /// Build the provider for this source
+    ///
+    /// `scheme` only applies to sources that yield a bare secret (env, file,
+    /// stdin); system stores always use their own platform's scheme.
+    /// `browser` selects the system store entry to read.
+    pub fn into_provider(
+        self,
+        scheme: KeyScheme,
+        browser: Browser,
+    ) -> Result<Box<dyn KeyProvider>, DecryptError> {
+        let provider: Box<dyn KeyProvider> = match self {
+            #[cfg(target_os = "macos")]
+            KeySource::Keychain => Box::new(KeychainProvider {
+                browser,
+                options: SystemStoreOptions::default(),
+            }),
+            #[cfg(target_os = "linux")]
+            KeySource::SecretService => Box::new(SecretServiceProvider {
+                browser,
+                options: SystemStoreOptions::default(),
+            }),
+            #[cfg(target_os = "linux")]
+            KeySource::KWallet(wallet) => Box::new(KWalletProvider { browser, wallet }),
+            KeySource::Env(var) => Box::new(EnvKeyProvider { var, scheme }),
+            KeySource::File(path) => Box::new(FileKeyProvider { path, scheme }),
+            KeySource::Stdin => Box::new(StdinKeyProvider { scheme }),
+            #[allow(unreachable_patterns)]
+            other => {
+                return Err(DecryptError::KeychainError(format!(
+                    "Key source {:?} is not supported on this platform",
+                    other
+                )));
+            }
+        };
+        Ok(provider)
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+
+        match (kind, value) {
+            ("keychain", None) => Ok(KeySource::Keychain),
+            ("secret-service", None) => Ok(KeySource::SecretService),
+            ("kwallet", None) => Ok(KeySource::KWallet("kdewallet".to_string())),
+            ("kwallet", Some(wallet)) => Ok(KeySource::KWallet(wallet.to_string())),
+            ("env", Some(var)) if !var.is_empty() => Ok(KeySource::Env(var.to_string())),
+            ("file", Some(path)) if !path.is_empty() => Ok(KeySource::File(PathBuf::from(path))),
+            ("stdin", None) => Ok(KeySource::Stdin),
+            _ => Err(format!(
+                "invalid key source '{}' (expected keychain, secret-service, kwallet[:WALLET], env:VAR, file:PATH or stdin)",
+                s
+            )),
+        }
+    }
+}
+
+/// Strip a single trailing "\n" or "\r\n"
+fn trim_line_ending(s: &str) -> &str {
+    s.strip_suffix('\n')
+        .map(|s| s.strip_suffix('\r').unwrap_or(s))
+        .unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_source() {
+        assert_eq!("keychain".parse(), Ok(KeySource::Keychain));
+        assert_eq!(
+            "env:CHROME_KEY".parse(),
+            Ok(KeySource::Env("CHROME_KEY".to_string()))
+        );
+        assert_eq!(
+            "file:/tmp/key".parse(),
+            Ok(KeySource::File(PathBuf::from("/tmp/key")))
+        );
+        assert_eq!(
+            "kwallet".parse(),
+            Ok(KeySource::KWallet("kdewallet".to_string()))
+        );
+        assert!("env:".parse::<KeySource>().is_err());
+        assert!("bogus".parse::<KeySource>().is_err());
+        assert!("password:hunter2".parse::<KeySource>().is_err());
+    }
+
+    #[test]
+    fn test_file_provider_ignores_trailing_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, "secret\n").unwrap();
+
+        let provider = FileKeyProvider {
+            path,
+            scheme: KeyScheme::Linux,
+        };
+        assert_eq!(
+            provider.derived_keys().unwrap(),
+            DerivedKeys::for_linux(Some("secret"))
+        );
+    }
+}
@@ -1,72 +1,150 @@
+use crate::browser::Browser;
+use crate::cache::MasterPasswordCache;
 use crate::models::DecryptError;
-use security_framework::passwords::get_generic_password;
 
-/// Chrome service and account names in keychain
-const CHROME_SERVICE: &str = "Chrome Safe Storage";
-const CHROME_ACCOUNT: &str = "Chrome";
-
-/// Get Chrome master password from macOS keychain
+/// Get a browser's master password from macOS keychain
 ///
 /// Chrome stores the master password in the system keychain
-/// Service name: "Chrome Safe Storage"
-/// Account name: "Chrome"
-pub fn get_chrome_master_password() -> Result<String, DecryptError> {
-    tracing::debug!("Attempting to retrieve Chrome master password from keychain");
-    let password_bytes = get_generic_password(CHROME_SERVICE, CHROME_ACCOUNT)
-        .map_err(|e| DecryptError::KeychainError(format!("Failed to get password: {}", e)))?;
+/// Service name: "Chrome Safe Storage" ("Brave Safe Storage", ...)
+/// Account name: "Chrome" ("Brave", ...)
+#[cfg(target_os = "macos")]
+pub fn get_chrome_master_password(browser: Browser) -> Result<String, DecryptError> {
+    use security_framework::passwords::get_generic_password;
+
+    tracing::debug!(
+        "Attempting to retrieve {} master password from keychain",
+        browser
+    );
+    let password_bytes = get_generic_password(
+        &browser.safe_storage_service(),
+        browser.safe_storage_account(),
+    )
+    .map_err(|e| DecryptError::KeychainError(format!("Failed to get password: {}", e)))?;
 
     tracing::debug!("Successfully retrieved master password from keychain");
     String::from_utf8(password_bytes)
         .map_err(|e| DecryptError::KeychainError(format!("Invalid UTF-8 in password: {}", e)))
 }
 
-/// Cache master password to local file (optional)
-pub fn cache_master_password(password: &str) -> Result<(), DecryptError> {
-    let cache_dir = dirs::home_dir()
-        .ok_or_else(|| DecryptError::IoError("Cannot find home directory".to_string()))?
-        .join(".chrome-password-unlock");
+/// Get a browser's master password from the freedesktop Secret Service
+///
+/// Chrome on Linux stores the "v11" secret in GNOME Keyring / KWallet
+/// Label: "Chrome Safe Storage" ("Brave Safe Storage", ...)
+/// Attribute: application = "chrome" ("brave", ...)
+#[cfg(target_os = "linux")]
+pub fn get_chrome_master_password(browser: Browser) -> Result<String, DecryptError> {
+    use secret_service::EncryptionType;
+    use secret_service::blocking::SecretService;
+    use std::collections::HashMap;
+
+    tracing::debug!(
+        "Attempting to retrieve {} master password from Secret Service",
+        browser
+    );
+    let service_label = browser.safe_storage_service();
+    let service = SecretService::connect(EncryptionType::Dh).map_err(|e| {
+        DecryptError::KeychainError(format!("Failed to connect to Secret Service: {}", e))
+    })?;
+
+    let items = service
+        .search_items(HashMap::from([(
+            "application",
+            browser.secret_service_application(),
+        )]))
+        .map_err(|e| DecryptError::KeychainError(format!("Failed to search items: {}", e)))?;
 
-    std::fs::create_dir_all(&cache_dir)?;
+    let item = items
+        .unlocked
+        .into_iter()
+        .chain(items.locked)
+        .find(|item| item.get_label().is_ok_and(|label| label == service_label))
+        .ok_or_else(|| DecryptError::KeychainError(format!("No '{}' item found", service_label)))?;
 
-    let cache_file = cache_dir.join("master_password");
-    std::fs::write(cache_file, password)?;
-    tracing::debug!("Cached master password to local file");
+    item.ensure_unlocked()
+        .map_err(|e| DecryptError::KeychainError(format!("Failed to unlock item: {}", e)))?;
+    let password_bytes = item
+        .get_secret()
+        .map_err(|e| DecryptError::KeychainError(format!("Failed to get password: {}", e)))?;
 
-    Ok(())
+    tracing::debug!("Successfully retrieved master password from Secret Service");
+    String::from_utf8(password_bytes)
+        .map_err(|e| DecryptError::KeychainError(format!("Invalid UTF-8 in password: {}", e)))
 }
 
-/// Load master password from cache file
-pub fn load_cached_master_password() -> Result<String, DecryptError> {
-    let cache_dir = dirs::home_dir()
-        .ok_or_else(|| DecryptError::IoError("Cannot find home directory".to_string()))?
-        .join(".chrome-password-unlock");
-
-    let cache_file = cache_dir.join("master_password");
-
-    if cache_file.exists() {
-        tracing::debug!("Loading master password from cache");
-        std::fs::read_to_string(cache_file)
-            .map_err(|e| DecryptError::IoError(format!("Failed to read cache: {}", e)))
-    } else {
-        tracing::debug!("No cached master password found");
-        Err(DecryptError::IoError("Cache file not found".to_string()))
+/// Get a browser's master password from KDE Wallet
+///
+/// Chrome's KWallet backend stores the "v11" secret in
+/// Folder: "Chrome Keys" ("Brave Keys", ...)
+/// Entry: "Chrome Safe Storage" ("Brave Safe Storage", ...)
+///
+/// The lookup goes through the `kwallet-query` tool shipped with KDE.
+#[cfg(target_os = "linux")]
+pub fn get_kwallet_master_password(browser: Browser, wallet: &str) -> Result<String, DecryptError> {
+    tracing::debug!(
+        "Attempting to retrieve {} master password from KWallet '{}'",
+        browser,
+        wallet
+    );
+    let output = std::process::Command::new("kwallet-query")
+        .args([
+            "--read-password",
+            &browser.safe_storage_service(),
+            "--folder",
+            &browser.kwallet_folder(),
+            wallet,
+        ])
+        .output()
+        .map_err(|e| DecryptError::KeychainError(format!("Failed to run kwallet-query: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(DecryptError::KeychainError(format!(
+            "kwallet-query failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
     }
+
+    tracing::debug!("Successfully retrieved master password from KWallet");
+    let password = String::from_utf8(output.stdout)
+        .map_err(|e| DecryptError::KeychainError(format!("Invalid UTF-8 in password: {}", e)))?;
+    Ok(password.trim_end_matches('\n').to_string())
 }
 
-/// Get master password (prefer to read from cache first)
-pub fn get_master_password_with_cache() -> Result<String, DecryptError> {
-    // Try to read from cache
-    if let Ok(password) = load_cached_master_password() {
-        tracing::info!("Using cached master password");
-        return Ok(password);
+/// Get master password, preferring the encrypted cache when one is given
+///
+/// `is_valid` checks a candidate secret against real ciphertexts. A cached
+/// secret that fails it is stale (e.g. the keychain entry was regenerated):
+/// the cache is discarded and the secret is fetched again.
+pub fn get_master_password_with_cache(
+    browser: Browser,
+    cache: Option<&MasterPasswordCache>,
+    is_valid: &dyn Fn(&str) -> bool,
+) -> Result<String, DecryptError> {
+    if let Some(cache) = cache {
+        match cache.load() {
+            Ok(Some(password)) if is_valid(&password) => {
+                tracing::info!("Using cached master password");
+                return Ok(password);
+            }
+            Ok(Some(_)) => {
+                tracing::warn!(
+                    "Cached master password no longer decrypts any password, discarding it"
+                );
+                cache.invalidate()?;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Ignoring master password cache: {}", e),
+        }
     }
 
     // Get from keychain
     tracing::info!("Retrieving master password from keychain");
-    let password = get_chrome_master_password()?;
+    let password = get_chrome_master_password(browser)?;
 
-    // Cache to local
-    let _ = cache_master_password(&password);
+    if let Some(cache) = cache
+        && let Err(e) = cache.store(&password)
+    {
+        tracing::warn!("Failed to cache master password: {}", e);
+    }
 
     Ok(password)
 }
@@ -1,41 +1,127 @@
 //! Chrome Password Unlock - CPU (Chrome Password Unlock)
 //!
-//! This is a tool for unlocking Chrome browser saved passwords on macOS.
+//! This is a tool for unlocking Chrome browser saved passwords on macOS and Linux.
 //!
 //! # Features
-//! - Get Chrome master password from macOS keychain
+//! - Get Chrome master password from macOS keychain or Linux Secret Service
+//! - Support Chromium-family browsers (Chromium, Brave, Edge, Vivaldi, Opera, Chrome Beta/Dev/Canary)
+//! - Pluggable key providers (keychain, environment, file, stdin, fixed key)
 //! - Support multiple Chrome profiles
 //! - Decrypt and display saved login information
+//! - Report rows that could not be decrypted instead of dropping them
 //! - Support filtering by keyword
+//! - Merge and deduplicate logins across profiles and browsers
+//! - Diff two snapshots (profiles, `Login Data` files or exports) of saved logins
 //! - Support JSON and table format output
+//! - Export to and import from Chrome's password CSV format
+//! - Import CSV/JSON/Bitwarden records back into a profile's `Login Data`
+//! - Re-encrypt a profile's passwords under a new Safe Storage secret
+//! - Export to Firefox CSV, Bitwarden JSON, 1Password (1PUX), encrypted KeePass (KDBX 4) databases and pass/gopass stores
+//! - Encrypt exports and query output with age (recipients or passphrase)
+//! - Audit passwords for weak, reused and old passwords and plain HTTP origins
+//! - Check passwords against a local Have I Been Pwned dump
 
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+compile_error!("chrome-password-unlock only supports macOS and Linux");
+
+pub mod audit;
+pub mod bitwarden;
+pub mod breach;
+pub mod browser;
+pub mod cache;
+pub mod chrome_csv;
 pub mod crypto;
+pub mod csv;
 pub mod database;
+pub mod diff;
+pub mod encrypted_output;
+pub mod export;
+pub mod firefox_csv;
+pub mod import;
+pub mod kdbx;
+pub mod key_provider;
 pub mod keychain;
+pub mod merge;
 pub mod models;
+pub mod onepux;
 pub mod output;
+pub mod pass_store;
 pub mod profile;
+pub mod rekey;
+
+#[cfg(test)]
+mod test_support;
 
-use crate::crypto::derive_key;
-use crate::database::query_login_data;
-use crate::keychain::get_master_password_with_cache;
-use crate::models::LoginInfo;
-use crate::profile::{ChromeProfile, find_chrome_profiles};
+use crate::browser::Browser;
+use crate::crypto::DerivedKeys;
+use crate::database::{SnapshotMode, query_login_rows};
+use crate::import::{ImportSummary, import_logins};
+use crate::key_provider::{KeyProvider, default_provider};
+use crate::models::{LoginInfo, LoginRow, PasswordStore, StoreSelection};
+use crate::profile::{ChromeProfile, find_browser_profiles};
 
 /// Chrome Password Unlocker (CPU - Chrome Password Unlock)
 pub struct ChromePasswordUnlock {
-    derived_key: Vec<u8>,
+    browser: Browser,
+    derived_keys: DerivedKeys,
+    snapshot_mode: SnapshotMode,
+    stores: StoreSelection,
 }
 
 impl ChromePasswordUnlock {
-    /// Create a new unlocker instance
+    /// Create a new unlocker instance using the platform's key store
     pub fn new() -> Result<Self, crate::models::DecryptError> {
-        tracing::debug!("Initializing ChromePasswordUnlock");
-        let master_password = get_master_password_with_cache()?;
-        let derived_key = derive_key(&master_password);
+        Self::for_browser(Browser::Chrome)
+    }
+
+    /// Create an unlocker for another Chromium-family browser, using its
+    /// entry in the platform's key store
+    pub fn for_browser(browser: Browser) -> Result<Self, crate::models::DecryptError> {
+        Ok(Self::with_key_provider(default_provider(browser).as_ref())?.with_browser(browser))
+    }
+
+    /// Create a new unlocker instance with keys from the given provider
+    pub fn with_key_provider(
+        provider: &dyn KeyProvider,
+    ) -> Result<Self, crate::models::DecryptError> {
+        tracing::debug!(
+            "Initializing ChromePasswordUnlock with {} key provider",
+            provider.name()
+        );
+        let derived_keys = provider.derived_keys()?;
         tracing::debug!("Successfully derived encryption key");
 
-        Ok(Self { derived_key })
+        Ok(Self {
+            browser: Browser::Chrome,
+            derived_keys,
+            snapshot_mode: SnapshotMode::default(),
+            stores: StoreSelection::default(),
+        })
+    }
+
+    /// Choose which browser's profiles are scanned by the `*_all_profiles`
+    /// methods
+    pub fn with_browser(mut self, browser: Browser) -> Self {
+        self.browser = browser;
+        self
+    }
+
+    /// Browser whose profiles are scanned
+    pub fn browser(&self) -> Browser {
+        self.browser
+    }
+
+    /// Choose how `Login Data` files are read (backup snapshot or in place)
+    pub fn with_snapshot_mode(mut self, snapshot_mode: SnapshotMode) -> Self {
+        self.snapshot_mode = snapshot_mode;
+        self
+    }
+
+    /// Choose which password stores (`Login Data`, `Login Data For
+    /// Account`) are read; both by default
+    pub fn with_stores(mut self, stores: StoreSelection) -> Self {
+        self.stores = stores;
+        self
     }
 
     /// Decrypt passwords from a specific Chrome profile
@@ -45,20 +131,72 @@ impl ChromePasswordUnlock {
         keyword: Option<&str>,
     ) -> Result<Vec<LoginInfo>, crate::models::DecryptError> {
         tracing::debug!("Decrypting profile: {}", profile.name);
+        let rows = self.scan_profile(profile, keyword)?;
+        Ok(rows
+            .into_iter()
+            .filter(|row| row.outcome.is_decrypted())
+            .map(|row| row.info)
+            .collect())
+    }
+
+    /// Read every login row of a profile, including rows that could not be
+    /// decrypted, with a per-row outcome
+    ///
+    /// Rows of the selected stores are concatenated, local store first; each
+    /// row's `store` tells where it came from.
+    pub fn scan_profile(
+        &self,
+        profile: &ChromeProfile,
+        keyword: Option<&str>,
+    ) -> Result<Vec<LoginRow>, crate::models::DecryptError> {
+        tracing::debug!("Scanning profile: {}", profile.name);
         let params = crate::models::QueryParams {
             keyword: keyword.map(|s| s.to_string()),
-            derived_key: &self.derived_key,
+            derived_keys: &self.derived_keys,
+            snapshot: self.snapshot_mode,
         };
 
-        query_login_data(&profile.login_data_path, &params)
+        // A selected store the profile does not have contributes no rows
+        let databases = profile.databases();
+        if databases.is_empty() {
+            return Err(crate::models::DecryptError::LoginDataNotFound);
+        }
+        let databases: Vec<_> = databases
+            .into_iter()
+            .filter(|(store, _)| self.stores.includes(*store))
+            .collect();
+
+        let mut rows = Vec::new();
+        for (store, db_path) in databases {
+            tracing::debug!("Reading {} store: {}", store.as_str(), db_path.display());
+            for mut row in query_login_rows(db_path, &params)? {
+                row.info.store = store;
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Read every login row of all profiles of the browser
+    pub fn scan_all_profiles(
+        &self,
+        keyword: Option<&str>,
+    ) -> Vec<(String, Result<Vec<LoginRow>, crate::models::DecryptError>)> {
+        Self::list_profiles_for(self.browser)
+            .into_iter()
+            .map(|profile| {
+                let result = self.scan_profile(&profile, keyword);
+                (profile.name, result)
+            })
+            .collect()
     }
 
-    /// Decrypt passwords from all Chrome profiles
+    /// Decrypt passwords from all profiles of the browser
     pub fn decrypt_from_all_profiles(
         &self,
         keyword: Option<&str>,
     ) -> Vec<(String, Result<Vec<LoginInfo>, crate::models::DecryptError>)> {
-        let profiles = find_chrome_profiles();
+        let profiles = Self::list_profiles_for(self.browser);
 
         profiles
             .into_iter()
@@ -69,9 +207,37 @@ impl ChromePasswordUnlock {
             .collect()
     }
 
-    /// Get all available Chrome profiles
+    /// Insert logins into one of a profile's password stores
+    ///
+    /// Fails while the browser is running; see [`import::import_logins`].
+    pub fn import_into_profile(
+        &self,
+        profile: &ChromeProfile,
+        store: PasswordStore,
+        logins: &[LoginInfo],
+    ) -> Result<ImportSummary, crate::models::DecryptError> {
+        let (_, db_path) = profile
+            .databases()
+            .into_iter()
+            .find(|(candidate, _)| *candidate == store)
+            .ok_or(crate::models::DecryptError::LoginDataNotFound)?;
+        tracing::debug!(
+            "Importing {} login(s) into {}",
+            logins.len(),
+            db_path.display()
+        );
+        import_logins(db_path, &self.derived_keys, logins)
+    }
+
+    /// Get all profiles of a browser in its default data directory
+    pub fn list_profiles_for(browser: Browser) -> Vec<ChromeProfile> {
+        find_browser_profiles(browser)
+    }
+
+    /// Get all available Chrome profiles; shorthand for
+    /// `list_profiles_for(Browser::Chrome)`
     pub fn list_profiles() -> Vec<ChromeProfile> {
-        find_chrome_profiles()
+        Self::list_profiles_for(Browser::Chrome)
     }
 }
 
@@ -80,3 +246,102 @@ impl Default for ChromePasswordUnlock {
         Self::new().expect("Failed to initialize ChromePasswordUnlock")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_provider::{FixedKeyProvider, KeyScheme};
+    use crate::models::PasswordStore;
+    use crate::test_support::{TestLogin, create_login_db};
+
+    #[test]
+    fn test_decrypt_synthetic_profile_with_fixed_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = FixedKeyProvider::from_password("known-secret", KeyScheme::Linux);
+        let keys = provider.derived_keys().unwrap();
+        let login_data_path = dir.path().join("Login Data");
+        create_login_db(
+            &login_data_path,
+            &keys,
+            &[
+                TestLogin::new("https://example.com/login", "alice", "hunter2"),
+                TestLogin::new("https://github.com/session", "bob", "p@ss,word").version("v11"),
+            ],
+        );
+
+        let profile = ChromeProfile::new(Browser::Chrome, "Default", dir.path().to_path_buf());
+        let unlocker = ChromePasswordUnlock::with_key_provider(&provider).unwrap();
+
+        let results = unlocker.decrypt_from_profile(&profile, None).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].username, "alice");
+        assert_eq!(results[0].password, "hunter2");
+        assert_eq!(results[1].password, "p@ss,word");
+
+        let filtered = unlocker
+            .decrypt_from_profile(&profile, Some("github"))
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].username, "bob");
+    }
+
+    #[test]
+    fn test_scan_local_and_account_stores() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = FixedKeyProvider::from_password("known-secret", KeyScheme::Linux);
+        let keys = provider.derived_keys().unwrap();
+        create_login_db(
+            &dir.path().join("Login Data"),
+            &keys,
+            &[TestLogin::new("https://example.com/", "local-user", "one")],
+        );
+        create_login_db(
+            &dir.path().join("Login Data For Account"),
+            &keys,
+            &[TestLogin::new("https://example.org/", "synced-user", "two")],
+        );
+
+        let profile = ChromeProfile::new(Browser::Chrome, "Default", dir.path().to_path_buf());
+        let unlocker = ChromePasswordUnlock::with_key_provider(&provider).unwrap();
+
+        let both = unlocker.decrypt_from_profile(&profile, None).unwrap();
+        let stores: Vec<_> = both
+            .iter()
+            .map(|l| (l.username.as_str(), l.store))
+            .collect();
+        assert_eq!(
+            stores,
+            vec![
+                ("local-user", PasswordStore::Local),
+                ("synced-user", PasswordStore::Account)
+            ]
+        );
+
+        let account = unlocker
+            .with_stores(StoreSelection::Account)
+            .decrypt_from_profile(&profile, None)
+            .unwrap();
+        assert_eq!(account.len(), 1);
+        assert_eq!(account[0].password, "two");
+
+        // A profile that never synced has no account store: no rows, no error
+        std::fs::remove_file(dir.path().join("Login Data For Account")).unwrap();
+        let local_only = ChromeProfile::new(Browser::Chrome, "Default", dir.path().to_path_buf());
+        let account_unlocker = ChromePasswordUnlock::with_key_provider(&provider)
+            .unwrap()
+            .with_stores(StoreSelection::Account);
+        assert!(
+            account_unlocker
+                .decrypt_from_profile(&local_only, None)
+                .unwrap()
+                .is_empty()
+        );
+
+        let empty = tempfile::tempdir().unwrap();
+        let no_stores = ChromeProfile::new(Browser::Chrome, "Empty", empty.path().to_path_buf());
+        assert!(matches!(
+            account_unlocker.decrypt_from_profile(&no_stores, None),
+            Err(crate::models::DecryptError::LoginDataNotFound)
+        ));
+    }
+}
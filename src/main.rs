@@ -1,8 +1,45 @@
 use anyhow::Result;
 use chrome_password_unlock::ChromePasswordUnlock;
-use chrome_password_unlock::models::LoginInfoWithProfile;
-use chrome_password_unlock::output::print_results;
-use clap::Parser;
+use chrome_password_unlock::audit::{AuditOptions, DEFAULT_MAX_AGE_DAYS, audit_logins};
+use chrome_password_unlock::breach::{PwnedPasswords, build_index, check_logins};
+use chrome_password_unlock::browser::{Browser, BrowserSelection};
+use chrome_password_unlock::cache::{
+    MasterPasswordCache, cache_status, clear_cache, default_cache_dir, has_legacy_plaintext_cache,
+    parse_ttl,
+};
+use chrome_password_unlock::database::{SnapshotMode, sample_encrypted_passwords};
+use chrome_password_unlock::diff::diff_logins;
+use chrome_password_unlock::encrypted_output::{
+    DecryptionKey, OutputEncryption, decrypt_output, encrypt_output, is_encrypted, parse_recipient,
+};
+use chrome_password_unlock::export::{
+    ExportFormat, ExportOptions, export_logins, profile_folder_name,
+};
+use chrome_password_unlock::import::{ImportFormat, parse_import};
+use chrome_password_unlock::kdbx::{KdbxKey, generate_key_file};
+use chrome_password_unlock::key_provider::{KeyScheme, KeySource, default_provider};
+use chrome_password_unlock::key_provider::{SystemStoreOptions, system_store_provider};
+use chrome_password_unlock::merge::{MergeConflict, merge_logins};
+use chrome_password_unlock::models::{
+    LoginInfo, LoginInfoWithProfile, LoginRow, PasswordStore, RowSummary, SkippedLogin,
+    StoreSelection,
+};
+use chrome_password_unlock::output::write_private_file;
+use chrome_password_unlock::output::{
+    Column, DEFAULT_COLUMNS, format_audit_report, format_breach_report, format_login_diff,
+    format_logins_json, format_merge_report, format_results_table, format_row_summary,
+    format_skipped_table, logins_json_value,
+};
+use chrome_password_unlock::pass_store::export_pass_store;
+use chrome_password_unlock::profile::{
+    ChromeProfile, find_browser_profiles, find_profiles_in_user_data_dir, select_profile,
+};
+use chrome_password_unlock::rekey::rekey_database;
+use clap::{Parser, Subcommand};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tracing_subscriber::{EnvFilter, fmt};
 
 /// Chrome password unlock tool (CPU - Chrome Password Unlock)
@@ -11,27 +48,238 @@ use tracing_subscriber::{EnvFilter, fmt};
 #[command(about = "Chrome password unlock tool (CPU)", long_about = None)]
 #[command(version)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// List all Chrome profiles
     #[arg(long)]
     list: bool,
 
+    /// Browser to read: chrome, chrome-beta, chrome-dev, chrome-canary,
+    /// chromium, brave, edge, vivaldi, opera, or all
+    #[arg(
+        short,
+        long,
+        value_name = "BROWSER",
+        default_value = "chrome",
+        global = true
+    )]
+    browser: BrowserSelection,
+
     /// Query all passwords (requires confirmation)
     #[arg(long)]
     all: bool,
 
-    /// Profile name (e.g., Default, Profile 1)
-    #[arg(short, long)]
+    /// Profile directory name (e.g., Default, Profile 1), display name or email
+    #[arg(short, long, global = true)]
     profile: Option<String>,
 
     /// Filter by URL keyword
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     keyword: Option<String>,
 
+    /// Only logins Chrome's Password Checkup flagged as leaked or phished
+    /// (dismissed warnings excluded)
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Merge logins of all selected profiles and browsers: one per site
+    /// (eTLD+1) and username, keeping the newest password
+    #[arg(long, global = true)]
+    merge: bool,
+
     /// Output in JSON format
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     json: bool,
+
+    /// Columns to show, comma separated (e.g. url,username,date-created)
+    #[arg(long, value_enum, value_delimiter = ',', global = true)]
+    columns: Option<Vec<Column>>,
+
+    /// Also list rows that were not decrypted (with ciphertext prefix and length)
+    #[arg(long)]
+    include_failed: bool,
+
+    /// Read profiles from this user data directory (or profile directory)
+    /// instead of the browser's default location
+    #[arg(long, value_name = "PATH", global = true)]
+    user_data_dir: Option<PathBuf>,
+
+    /// Read a single Login Data file (e.g. restored from a backup)
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "user_data_dir",
+        global = true
+    )]
+    login_data: Option<PathBuf>,
+
+    /// Password stores to read: local (Login Data), account (Login Data For
+    /// Account, passwords saved to the Google account) or both
+    #[arg(long, value_enum, default_value = "both", global = true)]
+    store: StoreSelection,
+
+    /// Read Login Data in place (read-only, immutable) instead of snapshotting it;
+    /// changes Chrome has not checkpointed yet are not visible
+    #[arg(long, global = true)]
+    no_copy: bool,
+
+    /// Where to get the Safe Storage secret: keychain, secret-service,
+    /// kwallet[:WALLET], env:VAR, file:PATH, stdin (default: platform key store)
+    #[arg(long, value_name = "SOURCE", global = true)]
+    key_from: Option<KeySource>,
+
+    /// Key derivation scheme for secrets from env/file/stdin (default: current platform)
+    #[arg(long, value_enum, global = true)]
+    key_scheme: Option<KeyScheme>,
+
+    /// Cache the Safe Storage secret, encrypted under a passphrase
+    /// (read from $CPU_CACHE_PASSPHRASE or prompted)
+    #[arg(long, global = true)]
+    cache: bool,
+
+    /// How long a cached secret stays valid (e.g. 30m, 8h, 2d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_ttl, default_value = "1h", global = true)]
+    cache_ttl: Duration,
+
+    /// Encrypt query, export, diff, audit and breach-check output with age to
+    /// this recipient (age1...);
+    /// may be repeated. Decrypt with `decrypt-export` or `age -d`
+    #[arg(long, value_name = "RECIPIENT", value_parser = parse_recipient, global = true)]
+    encrypt_to: Vec<age::x25519::Recipient>,
+
+    /// Encrypt query, export, diff, audit and breach-check output with age
+    /// under a passphrase
+    /// (read from $CPU_OUTPUT_PASSPHRASE or prompted)
+    #[arg(long, conflicts_with = "encrypt_to", global = true)]
+    passphrase: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export passwords to a file (or stdout) for another password manager
+    Export {
+        /// Export format
+        #[arg(short, long, value_enum, default_value = "chrome-csv")]
+        format: ExportFormat,
+
+        /// Output file, written with 0600 permissions (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Put each profile into its own folder (bitwarden, pass)
+        #[arg(long)]
+        folder_per_profile: bool,
+
+        /// Key file protecting a KDBX export; a new one is created if it does
+        /// not exist. The master password is read from $CPU_EXPORT_PASSWORD
+        /// or prompted (may be empty when a key file is used)
+        #[arg(long, value_name = "FILE")]
+        key_file: Option<PathBuf>,
+    },
+    /// Import logins from a CSV or JSON export into a profile's Login Data
+    /// (the browser must not be running)
+    Import {
+        /// File to import; age encrypted files are decrypted first
+        input: PathBuf,
+
+        /// Import format
+        #[arg(short, long, value_enum, default_value = "chrome-csv")]
+        format: ImportFormat,
+
+        /// age identity file for an input encrypted with --encrypt-to
+        #[arg(short, long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+    },
+    /// Re-encrypt a profile's passwords and notes under a new Safe Storage
+    /// secret (the browser must not be running); a backup is kept
+    Rekey {
+        /// Secret the passwords are encrypted with now (same forms as --key-from)
+        #[arg(long, value_name = "SOURCE")]
+        old_key_from: KeySource,
+
+        /// Secret to encrypt with (default: platform key store)
+        #[arg(long, value_name = "SOURCE")]
+        new_key_from: Option<KeySource>,
+    },
+    /// Report weak, reused and old passwords and logins over plain HTTP
+    Audit {
+        /// Report passwords not changed for more than this many days
+        #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_MAX_AGE_DAYS)]
+        max_age_days: u32,
+    },
+    /// Check passwords against a local Have I Been Pwned Pwned Passwords dump
+    BreachCheck {
+        /// SHA-1 dump sorted by hash (HASH:COUNT lines) or an index built
+        /// with `breach-index`
+        #[arg(long, value_name = "FILE")]
+        hibp_file: PathBuf,
+    },
+    /// Build a binary index from a Pwned Passwords text dump for faster checks
+    BreachIndex {
+        /// SHA-1 text dump sorted by hash
+        input: PathBuf,
+        /// Index file to write
+        output: PathBuf,
+    },
+    /// Compare two snapshots of saved passwords: added, removed and changed
+    /// logins, matched by signon realm and username
+    Diff {
+        /// Older snapshot: a profile name, profile directory, Login Data file
+        /// or export (chrome-csv, json, bitwarden; also age encrypted)
+        a: String,
+
+        /// Newer snapshot, in any of the same forms
+        b: String,
+
+        /// Show the old and new password of changed logins
+        #[arg(long)]
+        show_passwords: bool,
+
+        /// age identity file for exports encrypted with --encrypt-to
+        #[arg(short, long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+    },
+    /// Decrypt output written with --encrypt-to or --passphrase to stdout
+    DecryptExport {
+        /// age encrypted file (binary or armored)
+        input: PathBuf,
+
+        /// age identity file for output encrypted with --encrypt-to; without
+        /// it the passphrase is read from $CPU_OUTPUT_PASSPHRASE or prompted
+        #[arg(short, long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+    },
+    /// Manage the encrypted master password cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Delete the cached secret (including plaintext caches from older versions)
+    Clear,
+    /// Show whether a cached secret exists and when it expires
+    Status,
 }
 
+/// Environment variable holding the cache passphrase
+const CACHE_PASSPHRASE_ENV: &str = "CPU_CACHE_PASSPHRASE";
+
+/// First bytes of every SQLite database, to tell `Login Data` from exports
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Environment variable holding the master password of encrypted exports
+const EXPORT_PASSWORD_ENV: &str = "CPU_EXPORT_PASSWORD";
+
+/// Environment variable holding the passphrase of age encrypted output
+const OUTPUT_PASSPHRASE_ENV: &str = "CPU_OUTPUT_PASSPHRASE";
+
+/// Number of encrypted blobs per profile used to detect a stale cached secret
+const VALIDATION_SAMPLES_PER_PROFILE: usize = 5;
+
 fn main() -> Result<()> {
     // Initialize logging system
     fmt()
@@ -39,45 +287,806 @@ fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+    if (cli.user_data_dir.is_some() || cli.login_data.is_some())
+        && cli.browser == BrowserSelection::All
+    {
+        anyhow::bail!("--user-data-dir and --login-data need a single --browser");
+    }
+    if (!cli.encrypt_to.is_empty() || cli.passphrase)
+        && (cli.list
+            || !matches!(
+                cli.command,
+                None | Some(
+                    Command::Export { .. }
+                        | Command::Diff { .. }
+                        | Command::Audit { .. }
+                        | Command::BreachCheck { .. }
+                )
+            ))
+    {
+        anyhow::bail!(
+            "--encrypt-to and --passphrase only apply to queries, `export`, `diff`, `audit` and `breach-check`"
+        );
+    }
+    if cli.merge
+        && (cli.list
+            || !matches!(
+                cli.command,
+                None | Some(
+                    Command::Export { .. } | Command::Audit { .. } | Command::BreachCheck { .. }
+                )
+            ))
+    {
+        anyhow::bail!("--merge only applies to queries, `export`, `audit` and `breach-check`");
+    }
 
-    if cli.list {
-        list_profiles();
-    } else if cli.keyword.is_none() && !cli.all {
+    if let Some(Command::Cache { action }) = &cli.command {
+        manage_cache(action, cli.browser)?;
+    } else if let Some(Command::Export {
+        format,
+        output,
+        folder_per_profile,
+        key_file,
+    }) = &cli.command
+    {
+        if key_file.is_some() && *format != ExportFormat::Kdbx {
+            anyhow::bail!("--key-file only applies to --format kdbx");
+        }
+        if *format == ExportFormat::Pass && (!cli.encrypt_to.is_empty() || cli.passphrase) {
+            anyhow::bail!("pass entries are already encrypted to the store's recipients");
+        }
+        let kdbx_key = match format {
+            ExportFormat::Kdbx => Some(kdbx_key(key_file.as_deref())?),
+            _ => None,
+        };
+        let options = ExportOptions {
+            folders: *folder_per_profile,
+            kdbx_key,
+        };
+        let encryption = output_encryption(&cli)?;
+        export_passwords(
+            &cli,
+            *format,
+            output.as_deref(),
+            &options,
+            encryption.as_ref(),
+        )?;
+    } else if let Some(Command::Import {
+        input,
+        format,
+        identity,
+    }) = &cli.command
+    {
+        import_passwords(&cli, input, *format, identity.as_deref())?;
+    } else if let Some(Command::Rekey {
+        old_key_from,
+        new_key_from,
+    }) = &cli.command
+    {
+        rekey_profile(&cli, old_key_from, new_key_from.as_ref())?;
+    } else if let Some(Command::Audit { max_age_days }) = &cli.command {
+        audit_passwords(&cli, *max_age_days)?;
+    } else if let Some(Command::BreachCheck { hibp_file }) = &cli.command {
+        breach_check(&cli, hibp_file)?;
+    } else if let Some(Command::BreachIndex { input, output }) = &cli.command {
+        let count = build_index(input, output)?;
+        println!("📇 Indexed {} hash(es) into {}", count, output.display());
+    } else if let Some(Command::Diff {
+        a,
+        b,
+        show_passwords,
+        identity,
+    }) = &cli.command
+    {
+        diff_snapshots(&cli, [a, b], *show_passwords, identity.as_deref())?;
+    } else if let Some(Command::DecryptExport { input, identity }) = &cli.command {
+        decrypt_export(input, identity.as_deref())?;
+    } else if cli.list {
+        list_profiles(&cli)?;
+    } else if cli.keyword.is_none() && !cli.all && !cli.insecure {
         // No keyword and not --all, show statistics
-        show_password_statistics();
+        show_password_statistics(&cli);
+    } else {
+        query_passwords(&cli)?;
+    }
+
+    Ok(())
+}
+
+/// Create one unlocker per selected browser, with the key source selected on
+/// the command line
+///
+/// With `--browser all`, browsers without profiles are skipped and a browser
+/// whose key cannot be read is reported without aborting the others.
+fn create_unlockers(cli: &Cli) -> Result<Vec<ChromePasswordUnlock>> {
+    let browsers = match cli.browser {
+        BrowserSelection::All => Browser::ALL
+            .into_iter()
+            .filter(|&browser| browser_profiles(cli, browser).is_ok_and(|p| !p.is_empty()))
+            .collect(),
+        BrowserSelection::Only(browser) => vec![browser],
+    };
+    let passphrase = if cli.cache && cli.key_from.is_none() && !browsers.is_empty() {
+        Some(cache_passphrase()?)
+    } else {
+        None
+    };
+
+    let mut unlockers = Vec::new();
+    for browser in browsers {
+        match create_unlocker(cli, browser, passphrase.as_deref()) {
+            Ok(unlocker) => unlockers.push(unlocker),
+            Err(e) if cli.browser == BrowserSelection::All => {
+                eprintln!("❌ Failed to read the {} key: {}", browser, e)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if cli.cache && cli.key_from.is_some() {
+        tracing::warn!("--cache only applies to the system key store, ignoring it");
+    }
+    if let Ok(dir) = default_cache_dir()
+        && has_legacy_plaintext_cache(&dir)
+    {
+        tracing::warn!(
+            "A plaintext master password cache from an older version exists in {}, \
+             run `chrome-password-unlock cache clear` to remove it",
+            dir.display()
+        );
+    }
+
+    Ok(unlockers)
+}
+
+/// Create the unlocker of one browser
+fn create_unlocker(
+    cli: &Cli,
+    browser: Browser,
+    cache_passphrase: Option<&str>,
+) -> Result<ChromePasswordUnlock> {
+    let unlocker = match (&cli.key_from, cache_passphrase) {
+        (Some(source), _) => {
+            let scheme = cli.key_scheme.unwrap_or_else(KeyScheme::current);
+            let provider = source.clone().into_provider(scheme, browser)?;
+            ChromePasswordUnlock::with_key_provider(provider.as_ref())?.with_browser(browser)
+        }
+        (None, Some(passphrase)) => {
+            let cache = MasterPasswordCache::new(passphrase.to_string(), cli.cache_ttl)?
+                .for_browser(browser);
+            let options = SystemStoreOptions {
+                cache: Some(cache),
+                samples: validation_samples(&browser_profiles(cli, browser)?),
+            };
+            ChromePasswordUnlock::with_key_provider(
+                system_store_provider(browser, options).as_ref(),
+            )?
+            .with_browser(browser)
+        }
+        (None, None) => ChromePasswordUnlock::for_browser(browser)?,
+    };
+
+    let snapshot_mode = if cli.no_copy {
+        SnapshotMode::Immutable
+    } else {
+        SnapshotMode::Backup
+    };
+    Ok(unlocker
+        .with_snapshot_mode(snapshot_mode)
+        .with_stores(cli.store))
+}
+
+/// Read the cache passphrase from the environment or prompt for it
+fn cache_passphrase() -> Result<String> {
+    let passphrase = match std::env::var(CACHE_PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password("🔑 Cache passphrase: ")?,
+    };
+    if passphrase.is_empty() {
+        anyhow::bail!("Cache passphrase must not be empty");
+    }
+    Ok(passphrase)
+}
+
+/// Encrypted blobs from every profile, to detect a stale cached secret
+fn validation_samples(profiles: &[ChromeProfile]) -> Vec<Vec<u8>> {
+    profiles
+        .iter()
+        .flat_map(ChromeProfile::databases)
+        .filter_map(|(_, db_path)| {
+            sample_encrypted_passwords(db_path, VALIDATION_SAMPLES_PER_PROFILE).ok()
+        })
+        .flatten()
+        .collect()
+}
+
+/// Decrypted logins of the selected profiles, plus the rows that were skipped
+struct Collected {
+    logins: Vec<LoginInfoWithProfile>,
+    skipped: Vec<SkippedLogin>,
+    summary: RowSummary,
+    /// Logins dropped by `--merge`
+    duplicates: usize,
+    /// Accounts `--merge` found with different passwords
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Collected {
+    fn add(&mut self, browser: Browser, profile: &str, rows: Vec<LoginRow>) {
+        self.summary.merge(&RowSummary::from_rows(&rows));
+
+        for row in rows {
+            if row.outcome.is_decrypted() {
+                self.logins.push(LoginInfoWithProfile {
+                    browser,
+                    profile: profile.to_string(),
+                    info: row.info,
+                });
+            } else {
+                self.skipped.push(SkippedLogin {
+                    browser,
+                    profile: profile.to_string(),
+                    row,
+                });
+            }
+        }
+    }
+}
+
+/// Decrypt the selected profile (or all profiles) of every selected browser
+/// into a flat list
+fn collect_logins(cli: &Cli, unlockers: &[ChromePasswordUnlock]) -> Result<Collected> {
+    let keyword = cli.keyword.as_deref();
+    let mut collected = Collected {
+        logins: Vec::new(),
+        skipped: Vec::new(),
+        summary: RowSummary::default(),
+        duplicates: 0,
+        conflicts: Vec::new(),
+    };
+
+    if let Some(profile_name) = &cli.profile {
+        let mut found = false;
+        for unlocker in unlockers {
+            let profiles = browser_profiles(cli, unlocker.browser())?;
+            let Some(profile) = select_profile(profiles, profile_name)? else {
+                continue;
+            };
+            found = true;
+            let rows = unlocker.scan_profile(&profile, keyword)?;
+            collected.add(profile.browser, &profile.name, filter_rows(cli, rows));
+        }
+        if !found {
+            anyhow::bail!("Profile not found: {}", profile_name);
+        }
+    } else {
+        for unlocker in unlockers {
+            for profile in browser_profiles(cli, unlocker.browser())? {
+                match unlocker.scan_profile(&profile, keyword) {
+                    Ok(rows) => {
+                        collected.add(profile.browser, &profile.name, filter_rows(cli, rows))
+                    }
+                    Err(e) => eprintln!(
+                        "❌ Failed to decrypt {} profile '{}': {}",
+                        profile.browser, profile.name, e
+                    ),
+                }
+            }
+        }
+    }
+
+    if cli.merge {
+        let report = merge_logins(std::mem::take(&mut collected.logins));
+        // JSON queries carry the conflicts in their output
+        if !(cli.json && cli.command.is_none()) {
+            eprint!("{}", format_merge_report(&report));
+        }
+        collected.logins = report.logins;
+        collected.duplicates = report.duplicates;
+        collected.conflicts = report.conflicts;
+    }
+    Ok(collected)
+}
+
+/// Apply the row filters that are not part of the database query
+fn filter_rows(cli: &Cli, mut rows: Vec<LoginRow>) -> Vec<LoginRow> {
+    if cli.insecure {
+        rows.retain(|row| row.info.is_compromised());
+    }
+    rows
+}
+
+/// Handle the `export` subcommand
+fn export_passwords(
+    cli: &Cli,
+    format: ExportFormat,
+    output: Option<&Path>,
+    options: &ExportOptions,
+    encryption: Option<&OutputEncryption>,
+) -> Result<()> {
+    let unlockers = create_unlockers(cli)?;
+    let Collected {
+        logins, summary, ..
+    } = collect_logins(cli, &unlockers)?;
+    if let Some(line) = format_row_summary(&summary) {
+        eprintln!("{}", line);
+    }
+
+    if format == ExportFormat::Pass {
+        let Some(store) = output else {
+            anyhow::bail!("--format pass needs the password store directory as --output");
+        };
+        let count = export_pass_store(&logins, store, options.folders)?;
+        eprintln!(
+            "📦 Exported {} password(s) to the store at {}",
+            count,
+            store.display()
+        );
+        return Ok(());
+    }
+    let mut content = export_logins(&logins, format, options)?;
+    if let Some(encryption) = encryption {
+        content = encrypt_output(&content, encryption, output.is_none())?;
+    }
+
+    match output {
+        Some(path) => {
+            write_private_file(path, &content)?;
+            eprintln!(
+                "📦 Exported {} password(s) to {}",
+                logins.len(),
+                path.display()
+            );
+        }
+        None => std::io::stdout().write_all(&content)?,
+    }
+
+    Ok(())
+}
+
+/// age encryption selected with --encrypt-to or --passphrase
+fn output_encryption(cli: &Cli) -> Result<Option<OutputEncryption>> {
+    if !cli.encrypt_to.is_empty() {
+        return Ok(Some(OutputEncryption::Recipients(cli.encrypt_to.clone())));
+    }
+    if !cli.passphrase {
+        return Ok(None);
+    }
+
+    let passphrase = match std::env::var(OUTPUT_PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let passphrase = rpassword::prompt_password("🔑 Output passphrase: ")?;
+            if rpassword::prompt_password("🔑 Repeat passphrase: ")? != passphrase {
+                anyhow::bail!("Passphrases do not match");
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        anyhow::bail!("The output passphrase must not be empty");
+    }
+    Ok(Some(OutputEncryption::Passphrase(passphrase)))
+}
+
+/// Print collected output, ASCII-armored and encrypted when
+/// --encrypt-to/--passphrase is given
+fn print_output(output: &str, encryption: Option<OutputEncryption>) -> Result<()> {
+    match encryption {
+        Some(encryption) => {
+            std::io::stdout().write_all(&encrypt_output(output.as_bytes(), &encryption, true)?)?
+        }
+        None => print!("{}", output),
+    }
+    Ok(())
+}
+
+/// One side of `diff`
+enum Snapshot {
+    Profile(ChromeProfile),
+    Export(Vec<LoginInfo>),
+}
+
+/// Resolve a side of `diff`: an existing file is a `Login Data` database or
+/// an export, a directory a profile directory, anything else the name of a
+/// profile of the selected browser
+fn snapshot(cli: &Cli, browser: Browser, side: &str, identity: Option<&Path>) -> Result<Snapshot> {
+    let path = Path::new(side);
+    if path.is_file() {
+        let mut contents = std::fs::read(path)?;
+        if contents.starts_with(SQLITE_HEADER) {
+            return Ok(Snapshot::Profile(ChromeProfile::from_login_data(
+                browser, path,
+            )?));
+        }
+        if is_encrypted(&contents) {
+            contents = decrypt_output(&contents, &decryption_key(identity)?)?;
+        }
+        let contents = String::from_utf8(contents)?;
+        let logins = parse_import(&contents, ImportFormat::detect(&contents))?;
+        return Ok(Snapshot::Export(logins));
+    }
+    if path.is_dir() {
+        return Ok(Snapshot::Profile(ChromeProfile::from_dir(browser, path)?));
+    }
+    select_profile(browser_profiles(cli, browser)?, side)?
+        .map(Snapshot::Profile)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} is not a file, a directory or a {} profile",
+                side,
+                browser
+            )
+        })
+}
+
+/// Handle the `diff` subcommand
+fn diff_snapshots(
+    cli: &Cli,
+    sides: [&str; 2],
+    show_passwords: bool,
+    identity: Option<&Path>,
+) -> Result<()> {
+    let BrowserSelection::Only(browser) = cli.browser else {
+        anyhow::bail!("diff needs a single --browser");
+    };
+    let encryption = output_encryption(cli)?;
+    let snapshots = sides
+        .iter()
+        .map(|side| snapshot(cli, browser, side, identity))
+        .collect::<Result<Vec<_>>>()?;
+
+    let keyword = cli.keyword.as_deref();
+    let mut unlocker = None;
+    let mut logins = Vec::new();
+    for (side, snapshot) in sides.iter().zip(snapshots) {
+        let infos = match snapshot {
+            Snapshot::Profile(profile) => {
+                let unlocker = match &mut unlocker {
+                    Some(unlocker) => unlocker,
+                    slot => slot.insert(create_unlockers(cli)?.remove(0)),
+                };
+                let rows = unlocker.scan_profile(&profile, keyword)?;
+                if let Some(line) = format_row_summary(&RowSummary::from_rows(&rows)) {
+                    eprintln!("{}: {}", side, line);
+                }
+                rows.into_iter()
+                    .filter(|row| row.outcome.is_decrypted())
+                    .map(|row| row.info)
+                    .collect()
+            }
+            Snapshot::Export(mut infos) => {
+                if let Some(keyword) = keyword.map(str::to_lowercase) {
+                    infos.retain(|info| {
+                        [&info.url, &info.action_url, &info.signon_realm]
+                            .iter()
+                            .any(|url| url.to_lowercase().contains(&keyword))
+                    });
+                }
+                infos
+            }
+        };
+        logins.push(infos);
+    }
+
+    let diff = diff_logins(&logins[0], &logins[1], show_passwords);
+    let output = if cli.json {
+        format!("{}\n", serde_json::to_string_pretty(&diff)?)
     } else {
-        query_passwords(cli.profile, cli.keyword, cli.all, cli.json)?;
+        format_login_diff(&diff)
+    };
+    print_output(&output, encryption)?;
+
+    Ok(())
+}
+
+/// Handle the `decrypt-export` subcommand
+fn decrypt_export(input: &Path, identity: Option<&Path>) -> Result<()> {
+    let ciphertext = std::fs::read(input)?;
+    let plaintext = decrypt_output(&ciphertext, &decryption_key(identity)?)?;
+    std::io::stdout().write_all(&plaintext)?;
+    Ok(())
+}
+
+/// Identity file, or the passphrase from the environment or a prompt
+fn decryption_key(identity: Option<&Path>) -> Result<DecryptionKey> {
+    Ok(match identity {
+        Some(path) => DecryptionKey::Identities(std::fs::read_to_string(path)?),
+        None => DecryptionKey::Passphrase(match std::env::var(OUTPUT_PASSPHRASE_ENV) {
+            Ok(passphrase) => passphrase,
+            Err(_) => rpassword::prompt_password("🔑 Passphrase: ")?,
+        }),
+    })
+}
+
+/// The single profile a writing command works on, from --profile or
+/// --login-data
+fn target_profile(cli: &Cli, command: &str) -> Result<(Browser, ChromeProfile)> {
+    let BrowserSelection::Only(browser) = cli.browser else {
+        anyhow::bail!("{} needs a single --browser", command);
+    };
+    let profile = match (&cli.login_data, &cli.profile) {
+        (Some(_), _) => browser_profiles(cli, browser)?.remove(0),
+        (None, Some(name)) => select_profile(browser_profiles(cli, browser)?, name)?
+            .ok_or_else(|| anyhow::anyhow!("Profile not found: {}", name))?,
+        (None, None) => anyhow::bail!("{} needs --profile (or --login-data)", command),
+    };
+    Ok((browser, profile))
+}
+
+/// Handle the `rekey` subcommand
+fn rekey_profile(cli: &Cli, old_source: &KeySource, new_source: Option<&KeySource>) -> Result<()> {
+    if cli.key_from.is_some() {
+        anyhow::bail!("rekey takes --old-key-from and --new-key-from instead of --key-from");
+    }
+    let (browser, profile) = target_profile(cli, "rekey")?;
+    let scheme = cli.key_scheme.unwrap_or_else(KeyScheme::current);
+    let old = old_source
+        .clone()
+        .into_provider(scheme, browser)?
+        .derived_keys()?;
+    let new = match new_source {
+        Some(source) => source.clone().into_provider(scheme, browser)?,
+        None => default_provider(browser),
+    }
+    .derived_keys()?;
+    if old == new {
+        anyhow::bail!("The old and new keys are the same");
+    }
+
+    for (store, db_path) in profile.databases() {
+        if !cli.store.includes(store) {
+            continue;
+        }
+        let summary = rekey_database(db_path, &old, &new)?;
+        println!(
+            "🔁 Re-encrypted {} password(s) and {} note(s) in {}",
+            summary.passwords,
+            summary.notes,
+            db_path.display()
+        );
+        if summary.unchanged > 0 {
+            println!("   {} already used the new key", summary.unchanged);
+        }
+        println!("   Backup: {}", summary.backup.display());
+    }
+
+    Ok(())
+}
+
+/// Handle the `import` subcommand
+fn import_passwords(
+    cli: &Cli,
+    input: &Path,
+    format: ImportFormat,
+    identity: Option<&Path>,
+) -> Result<()> {
+    let (browser, profile) = target_profile(cli, "import")?;
+    let store = match cli.store {
+        StoreSelection::Account => PasswordStore::Account,
+        _ => PasswordStore::Local,
+    };
+
+    let mut contents = std::fs::read(input)?;
+    if is_encrypted(&contents) {
+        contents = decrypt_output(&contents, &decryption_key(identity)?)?;
+    }
+    let logins = parse_import(&String::from_utf8(contents)?, format)?;
+
+    let unlockers = create_unlockers(cli)?;
+    let summary = unlockers[0].import_into_profile(&profile, store, &logins)?;
+    println!(
+        "📥 Imported {} login(s) into {}",
+        summary.imported,
+        profile_folder_name(browser, &profile.name)
+    );
+    if summary.existing > 0 {
+        println!("   {} already saved, left unchanged", summary.existing);
+    }
+    if summary.invalid > 0 {
+        println!("   {} skipped without a URL or password", summary.invalid);
     }
 
     Ok(())
 }
 
-/// List all available Chrome profiles
-fn list_profiles() {
-    tracing::info!("Listing all Chrome profiles");
-    let profiles = ChromePasswordUnlock::list_profiles();
+/// Master password and key file of a KDBX export
+fn kdbx_key(key_file: Option<&Path>) -> Result<KdbxKey> {
+    let key_file = match key_file {
+        Some(path) if path.exists() => Some(std::fs::read(path)?),
+        Some(path) => {
+            let contents = generate_key_file();
+            write_private_file(path, &contents)?;
+            eprintln!("🔑 Created key file {}", path.display());
+            Some(contents)
+        }
+        None => None,
+    };
+
+    let password = match std::env::var(EXPORT_PASSWORD_ENV) {
+        Ok(password) => password,
+        Err(_) => {
+            let password = rpassword::prompt_password("🔑 KeePass master password: ")?;
+            if rpassword::prompt_password("🔑 Repeat master password: ")? != password {
+                anyhow::bail!("Passwords do not match");
+            }
+            password
+        }
+    };
+    if password.is_empty() && key_file.is_none() {
+        anyhow::bail!("A KDBX export needs a master password or --key-file");
+    }
+
+    Ok(KdbxKey {
+        password: (!password.is_empty()).then_some(password),
+        key_file,
+    })
+}
+
+/// Handle the `audit` subcommand
+fn audit_passwords(cli: &Cli, max_age_days: u32) -> Result<()> {
+    let encryption = output_encryption(cli)?;
+    let unlockers = create_unlockers(cli)?;
+    let Collected {
+        logins, summary, ..
+    } = collect_logins(cli, &unlockers)?;
+    let options = AuditOptions {
+        max_age_days,
+        ..Default::default()
+    };
+    let report = audit_logins(&logins, &options);
+
+    let mut output = String::new();
+    if cli.json {
+        writeln!(output, "{}", serde_json::to_string_pretty(&report)?)?;
+    } else {
+        output.push_str(&format_audit_report(&report));
+        if let Some(line) = format_row_summary(&summary) {
+            writeln!(output, "{}", line)?;
+        }
+    }
+    print_output(&output, encryption)
+}
+
+/// Handle the `breach-check` subcommand
+fn breach_check(cli: &Cli, hibp_file: &Path) -> Result<()> {
+    let encryption = output_encryption(cli)?;
+    let dump = PwnedPasswords::open(hibp_file)?;
+    let unlockers = create_unlockers(cli)?;
+    let Collected {
+        logins, summary, ..
+    } = collect_logins(cli, &unlockers)?;
+    let report = check_logins(&logins, &dump)?;
+
+    let mut output = String::new();
+    if cli.json {
+        writeln!(output, "{}", serde_json::to_string_pretty(&report)?)?;
+    } else {
+        output.push_str(&format_breach_report(&report));
+        if let Some(line) = format_row_summary(&summary) {
+            writeln!(output, "{}", line)?;
+        }
+    }
+    print_output(&output, encryption)
+}
+
+/// Handle the `cache` subcommand
+fn manage_cache(action: &CacheAction, browsers: BrowserSelection) -> Result<()> {
+    let dir = default_cache_dir()?;
+
+    match action {
+        CacheAction::Clear => {
+            clear_cache(&dir)?;
+            println!("🧹 Master password cache cleared");
+        }
+        CacheAction::Status => {
+            let mut legacy_plaintext = false;
+            for browser in browsers.browsers() {
+                let status = cache_status(&dir, browser)?;
+                legacy_plaintext |= status.legacy_plaintext;
+                match status.entry {
+                    Some((created_at, expires_at)) => {
+                        let now = SystemTime::now();
+                        println!("🔐 {} cache: {}", browser, status.path.display());
+                        if let Some(mode) = status.mode {
+                            println!("   Permissions: {:o}", mode);
+                        }
+                        if let Ok(age) = now.duration_since(created_at) {
+                            println!("   Created: {} ago", format_duration(age));
+                        }
+                        match expires_at.duration_since(now) {
+                            Ok(remaining) => {
+                                println!("   Expires in: {}", format_duration(remaining))
+                            }
+                            Err(_) => println!("   Expired"),
+                        }
+                    }
+                    None if browsers == BrowserSelection::All => {}
+                    None => println!("❌ No cached {} master password", browser),
+                }
+            }
+            if legacy_plaintext {
+                println!("⚠️  A plaintext cache from an older version exists, run `cache clear`");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Format a duration as e.g. "2h 5m"
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    match (days, hours) {
+        (0, 0) if minutes == 0 => format!("{}s", secs),
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// Profiles of a browser: its default location, or the directory or file
+/// given with `--user-data-dir` / `--login-data`
+fn browser_profiles(cli: &Cli, browser: Browser) -> Result<Vec<ChromeProfile>> {
+    if let Some(path) = &cli.login_data {
+        return Ok(vec![ChromeProfile::from_login_data(browser, path)?]);
+    }
+    if let Some(dir) = &cli.user_data_dir {
+        return Ok(find_profiles_in_user_data_dir(browser, dir)?);
+    }
+    Ok(find_browser_profiles(browser))
+}
+
+/// List all available profiles of the selected browsers
+fn list_profiles(cli: &Cli) -> Result<()> {
+    tracing::info!("Listing all profiles");
+    let mut profiles = Vec::new();
+    for browser in cli.browser.browsers() {
+        profiles.extend(browser_profiles(cli, browser)?);
+    }
 
     if profiles.is_empty() {
-        println!("❌ No Chrome profiles found");
-        println!("💡 Please ensure Chrome is installed and has been used at least once");
-        tracing::warn!("No Chrome profiles found");
-        return;
+        println!("❌ No browser profiles found");
+        println!("💡 Please ensure the browser is installed and has been used at least once");
+        tracing::warn!("No browser profiles found");
+        return Ok(());
     }
 
-    tracing::info!("Found {} Chrome profile(s)", profiles.len());
-    println!("🔍 Found {} Chrome profile(s):\n", profiles.len());
+    tracing::info!("Found {} profile(s)", profiles.len());
+    println!("🔍 Found {} profile(s):\n", profiles.len());
 
     for profile in profiles {
-        println!("  📁 {}", profile.name);
+        let title = profile_folder_name(profile.browser, &profile.name);
+        match &profile.display_name {
+            Some(display_name) => println!("  📁 {} — {}", title, display_name),
+            None => println!("  📁 {}", title),
+        }
+        if let Some(email) = &profile.email {
+            println!("     Account: {}", email);
+        }
+        if let Some(last_active) = profile.last_active {
+            println!(
+                "     Last active: {}",
+                last_active.format("%Y-%m-%d %H:%M:%S UTC")
+            );
+        }
+        if let Some(avatar_icon) = &profile.avatar_icon {
+            println!("     Avatar: {}", avatar_icon);
+        }
         println!("     Path: {}", profile.path.display());
-        println!("     Database: {}", profile.login_data_path.display());
+        for (store, db_path) in profile.databases() {
+            println!("     Database ({}): {}", store.as_str(), db_path.display());
+        }
         println!();
     }
+
+    Ok(())
 }
 
 /// Show password statistics
-fn show_password_statistics() {
-    let unlocker = match ChromePasswordUnlock::new() {
+fn show_password_statistics(cli: &Cli) {
+    let unlockers = match create_unlockers(cli) {
         Ok(u) => u,
         Err(e) => {
             eprintln!("❌ Failed to initialize: {}", e);
@@ -85,104 +1094,93 @@ fn show_password_statistics() {
         }
     };
 
-    let profiles = ChromePasswordUnlock::list_profiles();
-    let mut total_count = 0;
+    let mut profile_count = 0;
+    let mut summary = RowSummary::default();
 
-    for profile in &profiles {
-        if let Ok(results) = unlocker.decrypt_from_profile(profile, None) {
-            total_count += results.len();
+    for unlocker in &unlockers {
+        for profile in browser_profiles(cli, unlocker.browser()).unwrap_or_default() {
+            profile_count += 1;
+            if let Ok(rows) = unlocker.scan_profile(&profile, None) {
+                summary.merge(&RowSummary::from_rows(&rows));
+            }
         }
     }
 
     println!(
         "🔐 Found {} password(s) in {} profile(s)",
-        total_count,
-        profiles.len()
+        summary.decrypted, profile_count
     );
+    if let Some(line) = format_row_summary(&summary) {
+        println!("{}", line);
+    }
     println!();
     println!("💡 Use --keyword <term> to filter passwords");
     println!("💡 Use --all to show all passwords");
     println!("💡 Use --profile <name> to query specific profile");
+    if summary.skipped() > 0 {
+        println!("💡 Use --all --include-failed to list skipped rows");
+    }
 }
 
 /// Query passwords
-fn query_passwords(
-    profile: Option<String>,
-    keyword: Option<String>,
-    _all: bool,
-    json: bool,
-) -> Result<()> {
+///
+/// The output is collected first so it can be encrypted as a whole.
+fn query_passwords(cli: &Cli) -> Result<()> {
+    let encryption = output_encryption(cli)?;
     tracing::info!("Starting password decryption");
-    let query = ChromePasswordUnlock::new()?;
-
-    if let Some(profile_name) = profile {
-        // Query specific profile
-        tracing::info!("Decrypting specific profile: {}", profile_name);
-        let profiles = ChromePasswordUnlock::list_profiles();
-        let profile = profiles
-            .into_iter()
-            .find(|p| p.name == profile_name)
-            .ok_or_else(|| anyhow::anyhow!("Profile not found: {}", profile_name))?;
-
-        let results = query.decrypt_from_profile(&profile, keyword.as_deref())?;
-        tracing::info!(
-            "Successfully decrypted {} password(s) from profile '{}'",
-            results.len(),
-            profile_name
-        );
+    let unlockers = create_unlockers(cli)?;
+    let Collected {
+        logins,
+        skipped,
+        summary,
+        duplicates,
+        conflicts,
+    } = collect_logins(cli, &unlockers)?;
+    tracing::info!("Successfully decrypted {} password(s)", logins.len());
 
-        if json {
-            let json_results: Vec<LoginInfoWithProfile> = results
-                .into_iter()
-                .map(|info| LoginInfoWithProfile {
-                    profile: profile.name.clone(),
-                    info,
-                })
-                .collect();
-            println!("{}", serde_json::to_string_pretty(&json_results)?);
-        } else {
-            print_results(&results, Some(&profile.name));
+    let mut output = String::new();
+    if cli.json && (cli.include_failed || cli.merge) {
+        let logins = logins_json_value(&logins, cli.columns.as_deref())?;
+        let mut value = serde_json::json!({ "logins": logins });
+        if cli.include_failed {
+            value["skipped"] = serde_json::to_value(&skipped)?;
+        }
+        if cli.merge {
+            value["duplicates"] = duplicates.into();
+            value["conflicts"] = serde_json::to_value(&conflicts)?;
         }
+        writeln!(output, "{}", serde_json::to_string_pretty(&value)?)?;
+    } else if cli.json {
+        writeln!(
+            output,
+            "{}",
+            format_logins_json(&logins, cli.columns.as_deref())?
+        )?;
+    } else if logins.is_empty() {
+        writeln!(output, "❌ No passwords found")?;
     } else {
-        // Query all profiles
-        tracing::info!("Decrypting all Chrome profiles");
-        let all_results = query.decrypt_from_all_profiles(keyword.as_deref());
-
-        if json {
-            let all: Vec<LoginInfoWithProfile> = all_results
-                .into_iter()
-                .filter_map(|(name, result)| result.ok().map(|r| (name, r)))
-                .flat_map(|(name, results)| {
-                    results.into_iter().map(move |info| LoginInfoWithProfile {
-                        profile: name.clone(),
-                        info,
-                    })
-                })
-                .collect();
-
-            println!("{}", serde_json::to_string_pretty(&all)?);
-        } else {
-            let mut total_count = 0;
-            for (profile_name, result) in all_results {
-                match result {
-                    Ok(results) => {
-                        if !results.is_empty() {
-                            print_results(&results, Some(&profile_name));
-                            total_count += results.len();
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("❌ Failed to decrypt profile '{}': {}", profile_name, e);
-                    }
-                }
-            }
+        let columns = cli.columns.as_deref().unwrap_or(DEFAULT_COLUMNS);
+        for group in logins.chunk_by(|a, b| a.browser == b.browser && a.profile == b.profile) {
+            let results: Vec<_> = group.iter().map(|l| l.info.clone()).collect();
+            let title = profile_folder_name(group[0].browser, &group[0].profile);
+            writeln!(
+                output,
+                "{}",
+                format_results_table(&results, Some(&title), columns)
+            )?;
+        }
+    }
 
-            if total_count == 0 {
-                println!("❌ No passwords found");
-            }
+    if !cli.json {
+        if cli.include_failed && !skipped.is_empty() {
+            writeln!(output, "{}", format_skipped_table(&skipped))?;
+        } else if let Some(line) = format_row_summary(&summary) {
+            writeln!(output, "{} (use --include-failed to list them)", line)?;
         }
-        tracing::info!("Password decryption completed successfully");
     }
 
+    print_output(&output, encryption)?;
+    tracing::info!("Password decryption completed successfully");
+
     Ok(())
 }
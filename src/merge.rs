@@ -0,0 +1,250 @@
+//! Merge and deduplicate logins across profiles and browsers
+//!
+//! The same account is often saved in several profiles and browsers, under
+//! slightly different URLs (`http://example.com`, `https://www.example.com/`,
+//! `https://accounts.example.com/login`). Logins are grouped by site and
+//! username, where the site is the registrable domain (eTLD+1) of the login's
+//! realm, and the most recently changed password of each group is kept.
+
+use crate::browser::Browser;
+use crate::models::LoginInfoWithProfile;
+use chrono::{DateTime, Utc};
+use publicsuffix::{List, Psl};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use url::{Host, Url};
+
+/// Public Suffix List (ICANN and private domains), from publicsuffix.org
+static PUBLIC_SUFFIX_LIST: LazyLock<List> = LazyLock::new(|| {
+    include_str!("../data/public_suffix_list.dat")
+        .parse()
+        .expect("bundled public suffix list is valid")
+});
+
+/// Site and username a login is grouped by
+type AccountKey = (String, String);
+
+/// Merged logins and the groups whose passwords disagreed
+#[derive(Debug, Clone)]
+pub struct MergeReport {
+    /// One login per site and username, in the order of the input
+    pub logins: Vec<LoginInfoWithProfile>,
+    /// Logins dropped because a newer one for the same account was kept
+    pub duplicates: usize,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A site and username saved with different passwords
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MergeConflict {
+    pub site: String,
+    pub username: String,
+    /// Every copy of the login, the kept one first
+    pub sources: Vec<MergeSource>,
+}
+
+/// Where one copy of a conflicting login came from (without its password)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MergeSource {
+    pub browser: Browser,
+    pub profile: String,
+    pub url: String,
+    pub date_password_modified: Option<DateTime<Utc>>,
+}
+
+/// Deduplicate logins by site and username
+///
+/// Of each group the login with the newest `date_password_modified` (or
+/// `date_created` when the password was never changed) is kept, the first
+/// one on a tie. Groups with more than one distinct password are reported as
+/// conflicts.
+pub fn merge_logins(logins: Vec<LoginInfoWithProfile>) -> MergeReport {
+    let total = logins.len();
+    let mut groups: Vec<(AccountKey, Vec<(usize, LoginInfoWithProfile)>)> = Vec::new();
+    let mut index: HashMap<AccountKey, usize> = HashMap::new();
+
+    for (position, login) in logins.into_iter().enumerate() {
+        let realm = if login.info.signon_realm.is_empty() {
+            &login.info.url
+        } else {
+            &login.info.signon_realm
+        };
+        let key = (site_key(realm), login.info.username.clone());
+        let group = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[group].1.push((position, login));
+    }
+
+    let mut kept = Vec::with_capacity(groups.len());
+    let mut conflicts = Vec::new();
+    for ((site, username), mut members) in groups {
+        let newest = (0..members.len())
+            .reduce(|best, i| {
+                if changed_at(&members[i].1) > changed_at(&members[best].1) {
+                    i
+                } else {
+                    best
+                }
+            })
+            .unwrap_or_default();
+        let winner = members.remove(newest);
+
+        if members
+            .iter()
+            .any(|(_, login)| login.info.password != winner.1.info.password)
+        {
+            conflicts.push(MergeConflict {
+                site,
+                username,
+                sources: std::iter::once(&winner)
+                    .chain(&members)
+                    .map(|(_, login)| MergeSource {
+                        browser: login.browser,
+                        profile: login.profile.clone(),
+                        url: login.info.url.clone(),
+                        date_password_modified: changed_at(login),
+                    })
+                    .collect(),
+            });
+        }
+        kept.push(winner);
+    }
+
+    // Keep the input order, so logins of one profile stay together
+    kept.sort_by_key(|(position, _)| *position);
+    let logins: Vec<_> = kept.into_iter().map(|(_, login)| login).collect();
+    MergeReport {
+        duplicates: total - logins.len(),
+        logins,
+        conflicts,
+    }
+}
+
+/// Site a login belongs to: the registrable domain of an HTTP(S) URL,
+/// ignoring scheme, port, path and subdomains such as `www.`
+///
+/// IP addresses and single-label hosts (`localhost`) keep their port; other
+/// URLs, such as `android://` realms, only lose a trailing slash.
+pub fn site_key(url: &str) -> String {
+    let parsed = match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => parsed,
+        _ => return url.trim_end_matches('/').to_string(),
+    };
+    match parsed.host() {
+        Some(Host::Domain(domain)) if domain.contains('.') => {
+            registrable_domain(domain.trim_end_matches('.')).to_string()
+        }
+        Some(host) => match parsed.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        },
+        None => url.trim_end_matches('/').to_string(),
+    }
+}
+
+/// Public suffix plus one label (eTLD+1) of a lowercase domain name; a
+/// domain that is itself a public suffix (`github.io`) is its own site
+fn registrable_domain(domain: &str) -> &str {
+    match PUBLIC_SUFFIX_LIST.domain(domain.as_bytes()) {
+        Some(registrable) => &domain[domain.len() - registrable.as_bytes().len()..],
+        None => domain,
+    }
+}
+
+fn changed_at(login: &LoginInfoWithProfile) -> Option<DateTime<Utc>> {
+    login
+        .info
+        .date_password_modified
+        .or(login.info.date_created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::webkit_timestamp_to_datetime;
+    use crate::test_support::TestProfileLogin;
+
+    #[test]
+    fn test_site_key() {
+        assert_eq!(site_key("https://www.example.com/"), "example.com");
+        assert_eq!(site_key("http://example.com"), "example.com");
+        assert_eq!(
+            site_key("https://accounts.example.com:8443/login?next=/"),
+            "example.com"
+        );
+        assert_eq!(site_key("https://shop.example.co.uk/"), "example.co.uk");
+        assert_eq!(site_key("https://bbc.co.uk/"), "bbc.co.uk");
+        assert_eq!(site_key("https://alice.github.io/blog"), "alice.github.io");
+        assert_eq!(site_key("https://github.io/"), "github.io");
+        // Private suffixes: stores and buckets of different owners
+        assert_eq!(
+            site_key("https://admin.alice.myshopify.com/"),
+            "alice.myshopify.com"
+        );
+        assert_ne!(
+            site_key("https://alice.s3.amazonaws.com/"),
+            site_key("https://bob.s3.amazonaws.com/")
+        );
+        // Multi-label ccTLD suffix
+        assert_eq!(
+            site_key("https://www.city.kyotango.kyoto.jp/"),
+            "city.kyotango.kyoto.jp"
+        );
+        assert_eq!(site_key("https://WWW.Example.COM./"), "example.com");
+        assert_eq!(site_key("http://192.168.1.1:8080/"), "192.168.1.1:8080");
+        assert_eq!(site_key("http://localhost:3000/"), "localhost:3000");
+        assert_eq!(
+            site_key("android://hash@com.example.app/"),
+            "android://hash@com.example.app"
+        );
+    }
+
+    #[test]
+    fn test_merge_logins() {
+        // 2024-01-01 and 2024-01-02
+        let day_1 = webkit_timestamp_to_datetime(13_348_540_800_000_000);
+        let day_2 = webkit_timestamp_to_datetime(13_348_627_200_000_000);
+        let login = |realm: &str, username: &str, password: &str| {
+            TestProfileLogin::new(realm, username, password)
+                .signon_realm(realm)
+                .date_created(day_1)
+        };
+
+        let report = merge_logins(vec![
+            login("http://example.com/", "alice", "old").build(),
+            login("https://other.example/", "bob", "pw").build(),
+            login("https://other.example/", "bob", "pw")
+                .profile("Profile 1")
+                .build(),
+            login("https://www.example.com/", "alice", "new")
+                .browser(Browser::Brave)
+                .date_password_modified(day_2)
+                .build(),
+            login("https://example.com/", "carol", "pw")
+                .browser(Browser::Brave)
+                .build(),
+        ]);
+
+        let kept: Vec<_> = report
+            .logins
+            .iter()
+            .map(|l| (l.info.username.as_str(), l.info.password.as_str()))
+            .collect();
+        assert_eq!(kept, [("bob", "pw"), ("alice", "new"), ("carol", "pw")]);
+        assert_eq!(report.logins[0].profile, "Default");
+        assert_eq!(report.duplicates, 2);
+
+        // Same password in two profiles is a duplicate, not a conflict
+        assert_eq!(report.conflicts.len(), 1);
+        let conflict = &report.conflicts[0];
+        assert_eq!(
+            (conflict.site.as_str(), conflict.username.as_str()),
+            ("example.com", "alice")
+        );
+        assert_eq!(conflict.sources[0].browser, Browser::Brave);
+        assert_eq!(conflict.sources[0].date_password_modified, day_2);
+        assert_eq!(conflict.sources[1].browser, Browser::Chrome);
+    }
+}
@@ -1,14 +1,294 @@
+use chrono::{DateTime, Utc};
+
 /// Login information model
-#[derive(Debug, Clone, serde::Serialize)]
+///
+/// `url` is the login's `origin_url`, falling back to `action_url` for rows
+/// without one; the raw columns are kept alongside it.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct LoginInfo {
     pub url: String,
     pub username: String,
     pub password: String,
+    pub origin_url: String,
+    pub action_url: String,
+    pub signon_realm: String,
+    pub username_element: String,
+    pub password_element: String,
+    pub date_created: Option<DateTime<Utc>>,
+    pub date_last_used: Option<DateTime<Utc>>,
+    pub date_password_modified: Option<DateTime<Utc>>,
+    pub times_used: i64,
+    pub blacklisted_by_user: bool,
+    pub scheme: AuthScheme,
+    pub store: PasswordStore,
+    /// Decrypted notes from `password_notes` (empty if none)
+    pub note: String,
+    /// Password Checkup results from `insecure_credentials` (empty if none)
+    pub insecure: Vec<InsecureCredential>,
+}
+
+impl LoginInfo {
+    /// Whether Chrome flagged the password as leaked or phished (and the
+    /// warning was not dismissed)
+    pub fn is_compromised(&self) -> bool {
+        self.insecure
+            .iter()
+            .any(|credential| credential.insecurity_type.is_compromised() && !credential.is_muted)
+    }
+}
+
+/// Password Checkup verdict (`insecure_credentials.insecurity_type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsecurityType {
+    /// Found in a data breach
+    Leaked,
+    /// Entered on a phishing site
+    Phished,
+    Weak,
+    Reused,
+    /// Unknown value from a newer Chrome
+    Other,
+}
+
+impl InsecurityType {
+    /// Map Chrome's `InsecureType` value
+    pub fn from_db(value: i64) -> Self {
+        match value {
+            0 => InsecurityType::Leaked,
+            1 => InsecurityType::Phished,
+            2 => InsecurityType::Weak,
+            3 => InsecurityType::Reused,
+            _ => InsecurityType::Other,
+        }
+    }
+
+    /// Lowercase name, as used in JSON
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InsecurityType::Leaked => "leaked",
+            InsecurityType::Phished => "phished",
+            InsecurityType::Weak => "weak",
+            InsecurityType::Reused => "reused",
+            InsecurityType::Other => "other",
+        }
+    }
+
+    /// Leaked and phished passwords are what Chrome calls compromised
+    pub fn is_compromised(self) -> bool {
+        matches!(self, InsecurityType::Leaked | InsecurityType::Phished)
+    }
+}
+
+/// One Password Checkup result for a login
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct InsecureCredential {
+    #[serde(rename = "type")]
+    pub insecurity_type: InsecurityType,
+    /// When Chrome detected the problem
+    pub create_time: Option<DateTime<Utc>>,
+    /// Whether the user dismissed the warning
+    pub is_muted: bool,
+}
+
+/// Password database a login was read from
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordStore {
+    /// `Login Data`: passwords saved on this device
+    #[default]
+    Local,
+    /// `Login Data For Account`: passwords saved to the Google account
+    Account,
+}
+
+impl PasswordStore {
+    /// Database file name inside the profile directory
+    pub fn file_name(self) -> &'static str {
+        match self {
+            PasswordStore::Local => "Login Data",
+            PasswordStore::Account => "Login Data For Account",
+        }
+    }
+
+    /// Lowercase name, as used in JSON
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PasswordStore::Local => "local",
+            PasswordStore::Account => "account",
+        }
+    }
+}
+
+/// Which password stores to read (`--store`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StoreSelection {
+    Local,
+    Account,
+    #[default]
+    Both,
+}
+
+impl StoreSelection {
+    /// Whether `store` is selected
+    pub fn includes(self, store: PasswordStore) -> bool {
+        match self {
+            StoreSelection::Local => store == PasswordStore::Local,
+            StoreSelection::Account => store == PasswordStore::Account,
+            StoreSelection::Both => true,
+        }
+    }
+}
+
+/// Authentication scheme of a login (`logins.scheme`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// Regular web form
+    #[default]
+    Html,
+    /// HTTP Basic authentication
+    Basic,
+    /// HTTP Digest authentication
+    Digest,
+    /// Other HTTP authentication
+    Other,
+    /// Username-only form (first step of a multi-page login)
+    UsernameOnly,
+}
+
+impl AuthScheme {
+    /// Map Chrome's `PasswordForm::Scheme` value
+    pub fn from_db(value: i64) -> Self {
+        match value {
+            0 => AuthScheme::Html,
+            1 => AuthScheme::Basic,
+            2 => AuthScheme::Digest,
+            4 => AuthScheme::UsernameOnly,
+            _ => AuthScheme::Other,
+        }
+    }
+
+    /// Chrome's `PasswordForm::Scheme` value
+    pub fn to_db(self) -> i64 {
+        match self {
+            AuthScheme::Html => 0,
+            AuthScheme::Basic => 1,
+            AuthScheme::Digest => 2,
+            AuthScheme::Other => 3,
+            AuthScheme::UsernameOnly => 4,
+        }
+    }
+
+    /// Lowercase name, as used in JSON
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuthScheme::Html => "html",
+            AuthScheme::Basic => "basic",
+            AuthScheme::Digest => "digest",
+            AuthScheme::Other => "other",
+            AuthScheme::UsernameOnly => "username_only",
+        }
+    }
+}
+
+/// Outcome of reading one `logins` row
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RowOutcome {
+    /// Password decrypted successfully
+    Decrypted,
+    /// Password could not be decrypted (usually a different key)
+    Undecryptable {
+        reason: String,
+        version_prefix: Option<String>,
+        ciphertext_len: usize,
+    },
+    /// No password stored (e.g. username-only entries)
+    EmptyPassword,
+    /// "Never save" entry for a site (`blacklisted_by_user`)
+    NeverSave,
+}
+
+impl RowOutcome {
+    pub fn is_decrypted(&self) -> bool {
+        matches!(self, RowOutcome::Decrypted)
+    }
+
+    /// Short label for table output
+    pub fn label(&self) -> &'static str {
+        match self {
+            RowOutcome::Decrypted => "decrypted",
+            RowOutcome::Undecryptable { .. } => "undecryptable",
+            RowOutcome::EmptyPassword => "empty password",
+            RowOutcome::NeverSave => "never save",
+        }
+    }
+}
+
+/// A `logins` row with its outcome; `info.password` is empty unless decrypted
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LoginRow {
+    #[serde(flatten)]
+    pub info: LoginInfo,
+    #[serde(flatten)]
+    pub outcome: RowOutcome,
 }
 
-/// Login information with profile name (for JSON output)
+/// A row that was not decrypted, with its browser and profile name
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SkippedLogin {
+    pub browser: crate::browser::Browser,
+    pub profile: String,
+    #[serde(flatten)]
+    pub row: LoginRow,
+}
+
+/// Number of rows per outcome
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct RowSummary {
+    pub decrypted: usize,
+    pub undecryptable: usize,
+    pub empty_password: usize,
+    pub never_save: usize,
+}
+
+impl RowSummary {
+    /// Count the outcomes of `rows`
+    pub fn from_rows<'a>(rows: impl IntoIterator<Item = &'a LoginRow>) -> Self {
+        let mut summary = Self::default();
+        for row in rows {
+            match row.outcome {
+                RowOutcome::Decrypted => summary.decrypted += 1,
+                RowOutcome::Undecryptable { .. } => summary.undecryptable += 1,
+                RowOutcome::EmptyPassword => summary.empty_password += 1,
+                RowOutcome::NeverSave => summary.never_save += 1,
+            }
+        }
+        summary
+    }
+
+    /// Add the counts of another summary
+    pub fn merge(&mut self, other: &RowSummary) {
+        self.decrypted += other.decrypted;
+        self.undecryptable += other.undecryptable;
+        self.empty_password += other.empty_password;
+        self.never_save += other.never_save;
+    }
+
+    /// Rows that were read but not decrypted
+    pub fn skipped(&self) -> usize {
+        self.undecryptable + self.empty_password + self.never_save
+    }
+}
+
+/// Login information with browser and profile name (for JSON output)
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct LoginInfoWithProfile {
+    pub browser: crate::browser::Browser,
     pub profile: String,
     #[serde(flatten)]
     pub info: LoginInfo,
@@ -18,15 +298,32 @@ pub struct LoginInfoWithProfile {
 #[derive(Debug)]
 pub struct QueryParams<'a> {
     pub keyword: Option<String>,
-    pub derived_key: &'a [u8],
+    pub derived_keys: &'a crate::crypto::DerivedKeys,
+    pub snapshot: crate::database::SnapshotMode,
 }
 
 /// Chrome profile information
+///
+/// `name` is the profile's directory name; the display details come from the
+/// `Local State` file and are absent when it cannot be read. Each password
+/// database path is only set if the file exists.
 #[derive(Debug, Clone)]
 pub struct ChromeProfile {
+    pub browser: crate::browser::Browser,
     pub name: String,
     pub path: std::path::PathBuf,
-    pub login_data_path: std::path::PathBuf,
+    /// `Login Data` (passwords saved on this device)
+    pub login_data_path: Option<std::path::PathBuf>,
+    /// `Login Data For Account` (passwords saved to the Google account)
+    pub account_login_data_path: Option<std::path::PathBuf>,
+    /// Name shown in the profile picker (e.g. "Work")
+    pub display_name: Option<String>,
+    /// Email of the signed-in account
+    pub email: Option<String>,
+    /// Avatar resource (e.g. "chrome://theme/IDR_PROFILE_AVATAR_26")
+    pub avatar_icon: Option<String>,
+    /// When the profile was last used
+    pub last_active: Option<DateTime<Utc>>,
 }
 
 /// Decryption error type
@@ -47,8 +344,14 @@ pub enum DecryptError {
     #[error("Profile not found: {0}")]
     ProfileNotFound(String),
 
+    #[error("Ambiguous profile name: {0}")]
+    AmbiguousProfile(String),
+
     #[error("Login data file not found")]
     LoginDataNotFound,
+
+    #[error("Invalid input format: {0}")]
+    FormatError(String),
 }
 
 impl From<rusqlite::Error> for DecryptError {
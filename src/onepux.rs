@@ -0,0 +1,393 @@
+//! 1Password Unencrypted Export (1PUX)
+//!
+//! A zip archive holding `export.attributes` and `export.data`: one account
+//! with a vault per browser profile, and a login item per saved password.
+//! Entries are stored uncompressed.
+
+use crate::chrome_csv::display_name;
+use crate::export::{login_key, profile_folder_name};
+use crate::models::{DecryptError, LoginInfo, LoginInfoWithProfile};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// 1PUX format version written to `export.attributes`
+pub const EXPORT_VERSION: u32 = 3;
+
+/// 1Password category of login items
+pub const CATEGORY_LOGIN: &str = "001";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportAttributes {
+    version: u32,
+    description: &'static str,
+    created_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportData {
+    accounts: Vec<Account>,
+}
+
+#[derive(Debug, Serialize)]
+struct Account {
+    attrs: AccountAttrs,
+    vaults: Vec<Vault>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountAttrs {
+    account_name: String,
+    name: String,
+    avatar: String,
+    email: String,
+    uuid: String,
+    domain: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Vault {
+    attrs: VaultAttrs,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Serialize)]
+struct VaultAttrs {
+    uuid: String,
+    desc: String,
+    avatar: String,
+    name: String,
+    /// "U": user-created vault
+    #[serde(rename = "type")]
+    vault_type: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    uuid: String,
+    fav_index: u32,
+    created_at: i64,
+    updated_at: i64,
+    state: &'static str,
+    category_uuid: &'static str,
+    details: ItemDetails,
+    overview: ItemOverview,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemDetails {
+    login_fields: Vec<LoginField>,
+    notes_plain: String,
+    sections: Vec<serde_json::Value>,
+    password_history: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginField {
+    value: String,
+    id: String,
+    name: String,
+    /// "T": text, "P": password
+    field_type: &'static str,
+    designation: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct ItemOverview {
+    subtitle: String,
+    urls: Vec<OverviewUrl>,
+    title: String,
+    url: String,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct OverviewUrl {
+    label: String,
+    url: String,
+}
+
+/// Build a 1PUX archive; `created_at` is recorded as the export time
+pub fn format_1pux(
+    logins: &[LoginInfoWithProfile],
+    created_at: DateTime<Utc>,
+) -> Result<Vec<u8>, DecryptError> {
+    let mut vaults: Vec<Vault> = Vec::new();
+    for login in logins {
+        let name = profile_folder_name(login.browser, &login.profile);
+        let index = match vaults.iter().position(|vault| vault.attrs.name == name) {
+            Some(index) => index,
+            None => {
+                vaults.push(Vault {
+                    attrs: VaultAttrs {
+                        uuid: item_uuid(&[name.as_str()]),
+                        desc: String::new(),
+                        avatar: String::new(),
+                        name: name.clone(),
+                        vault_type: "U",
+                    },
+                    items: Vec::new(),
+                });
+                vaults.len() - 1
+            }
+        };
+        let uuid = item_uuid(&[login_key(login).as_str()]);
+        vaults[index]
+            .items
+            .push(to_item(&login.info, uuid, created_at));
+    }
+
+    let data = ExportData {
+        accounts: vec![Account {
+            attrs: AccountAttrs {
+                account_name: "Browser passwords".to_string(),
+                name: "Browser passwords".to_string(),
+                avatar: String::new(),
+                email: String::new(),
+                uuid: item_uuid(&["account"]),
+                domain: String::new(),
+            },
+            vaults,
+        }],
+    };
+    let attributes = ExportAttributes {
+        version: EXPORT_VERSION,
+        description: "1Password Unencrypted Export",
+        created_at: created_at.timestamp(),
+    };
+
+    Ok(write_stored_zip(
+        &[
+            ("export.attributes", to_json(&attributes)?.as_slice()),
+            ("export.data", to_json(&data)?.as_slice()),
+        ],
+        created_at,
+    ))
+}
+
+fn to_item(info: &LoginInfo, uuid: String, exported_at: DateTime<Utc>) -> Item {
+    let created = info.date_created.unwrap_or(exported_at);
+    let updated = info.date_password_modified.unwrap_or(created);
+    let urls = if info.url.is_empty() {
+        Vec::new()
+    } else {
+        vec![OverviewUrl {
+            label: String::new(),
+            url: info.url.clone(),
+        }]
+    };
+
+    Item {
+        uuid,
+        fav_index: 0,
+        created_at: created.timestamp(),
+        updated_at: updated.timestamp(),
+        state: "active",
+        category_uuid: CATEGORY_LOGIN,
+        details: ItemDetails {
+            login_fields: vec![
+                LoginField {
+                    value: info.username.clone(),
+                    id: String::new(),
+                    name: info.username_element.clone(),
+                    field_type: "T",
+                    designation: "username",
+                },
+                LoginField {
+                    value: info.password.clone(),
+                    id: String::new(),
+                    name: info.password_element.clone(),
+                    field_type: "P",
+                    designation: "password",
+                },
+            ],
+            notes_plain: info.note.clone(),
+            sections: Vec::new(),
+            password_history: Vec::new(),
+        },
+        overview: ItemOverview {
+            subtitle: info.username.clone(),
+            urls,
+            title: display_name(&info.url),
+            url: info.url.clone(),
+            tags: Vec::new(),
+        },
+    }
+}
+
+/// 26 lowercase characters like 1Password's own ids, stable across exports
+fn item_uuid(parts: &[&str]) -> String {
+    hex::encode(Sha256::digest(parts.join("\0").as_bytes()))[..26].to_string()
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, DecryptError> {
+    serde_json::to_vec(value)
+        .map_err(|e| DecryptError::FormatError(format!("Failed to serialize 1PUX: {}", e)))
+}
+
+/// Write a zip archive with uncompressed ("stored") entries
+fn write_stored_zip(files: &[(&str, &[u8])], modified: DateTime<Utc>) -> Vec<u8> {
+    const VERSION: u16 = 20;
+    const UTF8_NAMES: u16 = 1 << 11;
+    let dos_time =
+        ((modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2)) as u16;
+    let dos_date = (((modified.year() - 1980).max(0) as u32) << 9
+        | modified.month() << 5
+        | modified.day()) as u16;
+
+    let mut archive = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let offset = archive.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        let mut common = Vec::new();
+        common.extend_from_slice(&VERSION.to_le_bytes());
+        common.extend_from_slice(&UTF8_NAMES.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // stored
+        common.extend_from_slice(&dos_time.to_le_bytes());
+        common.extend_from_slice(&dos_date.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&common);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&VERSION.to_le_bytes()); // version made by
+        central.extend_from_slice(&common);
+        central.extend_from_slice(&[0; 6]); // comment length, disk, internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = archive.len() as u32;
+    archive.extend_from_slice(&central);
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    archive.extend_from_slice(&[0; 4]); // disk numbers
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&central_offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    archive
+}
+
+/// CRC-32 (IEEE) as used by zip
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::Browser;
+    use crate::test_support::TestProfileLogin;
+    use chrono::TimeZone;
+
+    /// Entries of a stored zip, read through the central directory
+    fn read_zip(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |at: usize| u16::from_le_bytes([archive[at], archive[at + 1]]) as usize;
+        let u32_at = |at: usize| u32::from_le_bytes(archive[at..at + 4].try_into().unwrap());
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(end), 0x0605_4b50);
+
+        let mut entry = u32_at(end + 16) as usize;
+        (0..u16_at(end + 10))
+            .map(|_| {
+                assert_eq!(u32_at(entry), 0x0201_4b50);
+                let (crc, size) = (u32_at(entry + 16), u32_at(entry + 24) as usize);
+                let name_len = u16_at(entry + 28);
+                let name = String::from_utf8(archive[entry + 46..][..name_len].to_vec()).unwrap();
+                let local = u32_at(entry + 42) as usize;
+                assert_eq!(u32_at(local), 0x0403_4b50);
+                let start = local + 30 + u16_at(local + 26) + u16_at(local + 28);
+                let data = archive[start..start + size].to_vec();
+                assert_eq!(crc32(&data), crc);
+                entry += 46 + name_len;
+                (name, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_format_1pux() {
+        let created = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let exported = Utc.with_ymd_and_hms(2026, 5, 2, 12, 30, 0).unwrap();
+        let login = |url: &str| {
+            TestProfileLogin::new(url, "alice", "hunter2")
+                .note("pin: 1234")
+                .date_created(Some(created))
+        };
+        let logins = vec![
+            login("https://www.example.com/").build(),
+            login("https://b.example/").browser(Browser::Brave).build(),
+            login("https://c.example/").build(),
+        ];
+
+        let archive = format_1pux(&logins, exported).unwrap();
+        let files = read_zip(&archive);
+        assert_eq!(files[0].0, "export.attributes");
+        assert_eq!(files[1].0, "export.data");
+
+        let attributes: serde_json::Value = serde_json::from_slice(&files[0].1).unwrap();
+        assert_eq!(attributes["version"], 3);
+        assert_eq!(attributes["createdAt"], exported.timestamp());
+
+        let data: serde_json::Value = serde_json::from_slice(&files[1].1).unwrap();
+        let vaults = data["accounts"][0]["vaults"].as_array().unwrap();
+        let names: Vec<_> = vaults.iter().map(|v| v["attrs"]["name"].clone()).collect();
+        assert_eq!(names, ["Default", "Default (Brave)"]);
+        assert_eq!(vaults[0]["items"].as_array().unwrap().len(), 2);
+
+        let item = &vaults[0]["items"][0];
+        assert_eq!(item["categoryUuid"], CATEGORY_LOGIN);
+        assert_eq!(item["createdAt"], created.timestamp());
+        assert_eq!(item["updatedAt"], created.timestamp());
+        assert_eq!(item["overview"]["title"], "example.com");
+        assert_eq!(item["overview"]["url"], "https://www.example.com/");
+        assert_eq!(item["details"]["notesPlain"], "pin: 1234");
+        let fields = &item["details"]["loginFields"];
+        assert_eq!(fields[0]["designation"], "username");
+        assert_eq!(fields[0]["value"], "alice");
+        assert_eq!(fields[1]["designation"], "password");
+        assert_eq!(fields[1]["value"], "hunter2");
+        assert_eq!(item["uuid"].as_str().unwrap().len(), 26);
+
+        // Rows that differ only in the form's field names are distinct items
+        let mut other_form = logins[0].clone();
+        other_form.info.password_element = "new-password".to_string();
+        let archive = format_1pux(&[logins[0].clone(), other_form], exported).unwrap();
+        let data: serde_json::Value = serde_json::from_slice(&read_zip(&archive)[1].1).unwrap();
+        let items = &data["accounts"][0]["vaults"][0]["items"];
+        assert_ne!(items[0]["uuid"], items[1]["uuid"]);
+    }
+}
@@ -1,20 +1,158 @@
-use crate::models::LoginInfo;
+use crate::audit::{AuditReport, score_label};
+use crate::breach::BreachReport;
+use crate::diff::{DiffEntry, LoginDiff};
+use crate::merge::MergeReport;
+use crate::models::{
+    DecryptError, LoginInfo, LoginInfoWithProfile, RowOutcome, RowSummary, SkippedLogin,
+};
+use chrono::{DateTime, Utc};
 use comfy_table::{Cell, Color, Table, presets::UTF8_FULL};
+use serde_json::{Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
 use tracing::{self, warn};
 
+/// A `LoginInfo` field that can be shown in table or JSON output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    Url,
+    Username,
+    Password,
+    OriginUrl,
+    ActionUrl,
+    SignonRealm,
+    UsernameElement,
+    PasswordElement,
+    DateCreated,
+    DateLastUsed,
+    DatePasswordModified,
+    TimesUsed,
+    BlacklistedByUser,
+    Scheme,
+    Store,
+    Note,
+    Insecure,
+}
+
+/// Columns shown when none are selected
+pub const DEFAULT_COLUMNS: &[Column] = &[Column::Url, Column::Username, Column::Password];
+
+impl Column {
+    /// Table header
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Url => "URL",
+            Column::Username => "Username",
+            Column::Password => "Password",
+            Column::OriginUrl => "Origin URL",
+            Column::ActionUrl => "Action URL",
+            Column::SignonRealm => "Signon Realm",
+            Column::UsernameElement => "Username Element",
+            Column::PasswordElement => "Password Element",
+            Column::DateCreated => "Created",
+            Column::DateLastUsed => "Last Used",
+            Column::DatePasswordModified => "Password Modified",
+            Column::TimesUsed => "Times Used",
+            Column::BlacklistedByUser => "Never Save",
+            Column::Scheme => "Scheme",
+            Column::Store => "Store",
+            Column::Note => "Note",
+            Column::Insecure => "Insecure",
+        }
+    }
+
+    /// JSON key (the `LoginInfo` field name)
+    pub fn key(self) -> &'static str {
+        match self {
+            Column::Url => "url",
+            Column::Username => "username",
+            Column::Password => "password",
+            Column::OriginUrl => "origin_url",
+            Column::ActionUrl => "action_url",
+            Column::SignonRealm => "signon_realm",
+            Column::UsernameElement => "username_element",
+            Column::PasswordElement => "password_element",
+            Column::DateCreated => "date_created",
+            Column::DateLastUsed => "date_last_used",
+            Column::DatePasswordModified => "date_password_modified",
+            Column::TimesUsed => "times_used",
+            Column::BlacklistedByUser => "blacklisted_by_user",
+            Column::Scheme => "scheme",
+            Column::Store => "store",
+            Column::Note => "note",
+            Column::Insecure => "insecure",
+        }
+    }
+
+    /// Text shown in the table
+    pub fn value(self, info: &LoginInfo) -> String {
+        match self {
+            Column::Url => info.url.clone(),
+            Column::Username => info.username.clone(),
+            Column::Password => info.password.clone(),
+            Column::OriginUrl => info.origin_url.clone(),
+            Column::ActionUrl => info.action_url.clone(),
+            Column::SignonRealm => info.signon_realm.clone(),
+            Column::UsernameElement => info.username_element.clone(),
+            Column::PasswordElement => info.password_element.clone(),
+            Column::DateCreated => format_datetime(info.date_created),
+            Column::DateLastUsed => format_datetime(info.date_last_used),
+            Column::DatePasswordModified => format_datetime(info.date_password_modified),
+            Column::TimesUsed => info.times_used.to_string(),
+            Column::BlacklistedByUser => info.blacklisted_by_user.to_string(),
+            Column::Scheme => info.scheme.as_str().to_string(),
+            Column::Store => info.store.as_str().to_string(),
+            Column::Note => info.note.clone(),
+            Column::Insecure => info
+                .insecure
+                .iter()
+                .map(|credential| match credential.is_muted {
+                    true => format!("{} (muted)", credential.insecurity_type.as_str()),
+                    false => credential.insecurity_type.as_str().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    fn color(self) -> Option<Color> {
+        match self {
+            Column::Url => Some(Color::Cyan),
+            Column::Username => Some(Color::Green),
+            Column::Password => Some(Color::Yellow),
+            _ => None,
+        }
+    }
+}
+
+/// Format an optional timestamp for table output
+fn format_datetime(datetime: Option<DateTime<Utc>>) -> String {
+    datetime
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
 /// Format query results as table output
-pub fn format_results_table(results: &[LoginInfo], profile_name: Option<&str>) -> String {
+pub fn format_results_table(
+    results: &[LoginInfo],
+    profile_name: Option<&str>,
+    columns: &[Column],
+) -> String {
     let mut table = Table::new();
     table
         .load_preset(UTF8_FULL)
-        .set_header(vec!["URL", "Username", "Password"]);
+        .set_header(columns.iter().map(|c| c.header()));
 
     for item in results {
-        table.add_row(vec![
-            Cell::new(&item.url).fg(Color::Cyan),
-            Cell::new(&item.username).fg(Color::Green),
-            Cell::new(&item.password).fg(Color::Yellow),
-        ]);
+        table.add_row(columns.iter().map(|column| {
+            let cell = Cell::new(column.value(item));
+            match column.color() {
+                Some(color) => cell.fg(color),
+                None => cell,
+            }
+        }));
     }
 
     let mut output = String::new();
@@ -36,13 +174,386 @@ pub fn format_results_json(results: &[LoginInfo]) -> String {
     serde_json::to_string_pretty(results).unwrap_or_else(|_| "[]".to_string())
 }
 
+/// Format logins as JSON, keeping only the selected columns (plus the
+/// browser and profile)
+pub fn format_logins_json(
+    logins: &[LoginInfoWithProfile],
+    columns: Option<&[Column]>,
+) -> Result<String, DecryptError> {
+    let value = logins_json_value(logins, columns)?;
+    serde_json::to_string_pretty(&value).map_err(|e| DecryptError::FormatError(e.to_string()))
+}
+
+/// JSON value of logins, keeping only the selected columns (plus the browser
+/// and profile)
+pub fn logins_json_value(
+    logins: &[LoginInfoWithProfile],
+    columns: Option<&[Column]>,
+) -> Result<Value, DecryptError> {
+    let to_json_error = |e: serde_json::Error| DecryptError::FormatError(e.to_string());
+
+    let Some(columns) = columns else {
+        return serde_json::to_value(logins).map_err(to_json_error);
+    };
+
+    let rows = logins
+        .iter()
+        .map(|login| {
+            let mut full = match serde_json::to_value(login).map_err(to_json_error)? {
+                Value::Object(map) => map,
+                _ => Map::new(),
+            };
+            let mut row = Map::new();
+            for key in ["browser", "profile"] {
+                if let Some(value) = full.remove(key) {
+                    row.insert(key.to_string(), value);
+                }
+            }
+            for column in columns {
+                let value = full.remove(column.key()).unwrap_or(Value::Null);
+                row.insert(column.key().to_string(), value);
+            }
+            Ok(Value::Object(row))
+        })
+        .collect::<Result<Vec<_>, DecryptError>>()?;
+
+    Ok(Value::Array(rows))
+}
+
+/// Describe the rows that were skipped, or `None` if there are none
+pub fn format_row_summary(summary: &RowSummary) -> Option<String> {
+    if summary.skipped() == 0 {
+        return None;
+    }
+
+    let parts: Vec<String> = [
+        (summary.undecryptable, "undecryptable"),
+        (summary.empty_password, "empty password"),
+        (summary.never_save, "never save"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, label)| format!("{} {}", count, label))
+    .collect();
+
+    Some(format!(
+        "⚠️  Skipped {} row(s): {}",
+        summary.skipped(),
+        parts.join(", ")
+    ))
+}
+
+/// Format skipped rows as a table, with ciphertext details for failures
+pub fn format_skipped_table(skipped: &[SkippedLogin]) -> String {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL).set_header(vec![
+        "Browser", "Profile", "URL", "Username", "Status", "Prefix", "Length", "Reason",
+    ]);
+
+    for item in skipped {
+        let (prefix, length, reason) = match &item.row.outcome {
+            RowOutcome::Undecryptable {
+                reason,
+                version_prefix,
+                ciphertext_len,
+            } => (
+                version_prefix.clone().unwrap_or_else(|| "-".to_string()),
+                ciphertext_len.to_string(),
+                reason.clone(),
+            ),
+            // Nothing was decrypted, so there is no ciphertext to describe
+            _ => (String::new(), String::new(), String::new()),
+        };
+
+        table.add_row(vec![
+            Cell::new(item.browser.name()),
+            Cell::new(&item.profile),
+            Cell::new(&item.row.info.url).fg(Color::Cyan),
+            Cell::new(&item.row.info.username).fg(Color::Green),
+            Cell::new(item.row.outcome.label()).fg(Color::Red),
+            Cell::new(prefix),
+            Cell::new(length),
+            Cell::new(reason),
+        ]);
+    }
+
+    format!(
+        "\n⚠️  Skipped rows\n{}\n\n📊 Total: {} record(s)\n",
+        table,
+        skipped.len()
+    )
+}
+
+/// Format an audit report: a summary followed by one table row per finding
+pub fn format_audit_report(report: &AuditReport) -> String {
+    let summary = &report.summary;
+    let scores: Vec<String> = summary
+        .scores
+        .iter()
+        .enumerate()
+        .map(|(score, count)| format!("{} {}", count, score_label(score as u8)))
+        .collect();
+    let mut out = format!("🩺 Audited {} password(s)\n", summary.total);
+    out.push_str(&format!("   Weak: {}\n", summary.weak));
+    out.push_str(&format!(
+        "   Reused: {} in {} group(s)\n",
+        summary.reused, summary.reuse_groups
+    ));
+    out.push_str(&format!("   Old: {}\n", summary.old));
+    out.push_str(&format!(
+        "   Insecure origin: {}\n",
+        summary.insecure_origin
+    ));
+    out.push_str(&format!("   Strength: {}\n", scores.join(", ")));
+
+    if report.findings.is_empty() {
+        out.push_str("\n✅ No problems found\n");
+        return out;
+    }
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL).set_header(vec![
+        "Browser", "Profile", "URL", "Username", "Finding", "Details",
+    ]);
+    for finding in &report.findings {
+        table.add_row(vec![
+            Cell::new(finding.browser.name()),
+            Cell::new(&finding.profile),
+            Cell::new(&finding.url).fg(Color::Cyan),
+            Cell::new(&finding.username).fg(Color::Green),
+            Cell::new(finding.kind.label()).fg(Color::Red),
+            Cell::new(finding.kind.details()),
+        ]);
+    }
+    out.push_str(&format!(
+        "\n{}\n\n📊 Total: {} finding(s)\n",
+        table,
+        report.findings.len()
+    ));
+    out
+}
+
+/// Format a breach check report: one table row per compromised login
+pub fn format_breach_report(report: &BreachReport) -> String {
+    if report.findings.is_empty() {
+        return format!(
+            "✅ None of {} password(s) found in the breach dump\n",
+            report.checked
+        );
+    }
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL).set_header(vec![
+        "Browser",
+        "Profile",
+        "URL",
+        "Username",
+        "Times Seen",
+    ]);
+    for finding in &report.findings {
+        table.add_row(vec![
+            Cell::new(finding.browser.name()),
+            Cell::new(&finding.profile),
+            Cell::new(&finding.url).fg(Color::Cyan),
+            Cell::new(&finding.username).fg(Color::Green),
+            Cell::new(finding.count).fg(Color::Red),
+        ]);
+    }
+    format!(
+        "\n🚨 Compromised passwords\n{}\n\n📊 {} of {} login(s) use a password found in breaches\n",
+        table,
+        report.findings.len(),
+        report.checked
+    )
+}
+
+/// Format the outcome of merging logins: a summary line and, for accounts
+/// saved with different passwords, a table of where each copy came from
+pub fn format_merge_report(report: &MergeReport) -> String {
+    let mut out = format!(
+        "🔀 Merged {} login(s) into {}, {} conflict(s)\n",
+        report.logins.len() + report.duplicates,
+        report.logins.len(),
+        report.conflicts.len()
+    );
+    if report.conflicts.is_empty() {
+        return out;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_header(vec!["Site", "Username", "Kept", "Other Passwords"]);
+    let source = |source: &crate::merge::MergeSource| {
+        let changed = source
+            .date_password_modified
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        format!(
+            "{} {} ({}, {})",
+            source.browser.name(),
+            source.profile,
+            source.url,
+            changed
+        )
+    };
+    for conflict in &report.conflicts {
+        let others: Vec<String> = conflict.sources[1..].iter().map(source).collect();
+        table.add_row(vec![
+            Cell::new(&conflict.site).fg(Color::Cyan),
+            Cell::new(&conflict.username).fg(Color::Green),
+            Cell::new(source(&conflict.sources[0])),
+            Cell::new(others.join("\n")).fg(Color::Yellow),
+        ]);
+    }
+    out.push_str(&format!(
+        "\n⚠️  Same site and username with different passwords, newest kept\n{}\n",
+        table
+    ));
+    out
+}
+
+/// Format a diff of two snapshots: one table row per added, removed or
+/// changed login, passwords only when the diff carries them
+pub fn format_login_diff(diff: &LoginDiff) -> String {
+    let summary = format!(
+        "📊 {} added, {} removed, {} changed, {} unchanged\n",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
+    if diff.is_empty() {
+        return format!("✅ No differences\n{}", summary);
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_header(vec!["Change", "Signon Realm", "Username", "Password"]);
+    let mut add_rows = |entries: &[DiffEntry], label: &str, color: Color| {
+        for entry in entries {
+            let password = match &entry.passwords {
+                Some(change) => format!("{} → {}", change.old, change.new),
+                None if label == "changed" => "changed".to_string(),
+                None => String::new(),
+            };
+            table.add_row(vec![
+                Cell::new(label).fg(color),
+                Cell::new(&entry.signon_realm).fg(Color::Cyan),
+                Cell::new(&entry.username).fg(Color::Green),
+                Cell::new(password),
+            ]);
+        }
+    };
+    add_rows(&diff.added, "added", Color::Green);
+    add_rows(&diff.removed, "removed", Color::Red);
+    add_rows(&diff.changed, "changed", Color::Yellow);
+
+    format!("{}\n\n{}", table, summary)
+}
+
 /// Print query results
-pub fn print_results(results: &[LoginInfo], profile_name: Option<&str>) {
+pub fn print_results(results: &[LoginInfo], profile_name: Option<&str>, columns: &[Column]) {
     if results.is_empty() {
         warn!("No passwords found for profile: {:?}", profile_name);
         println!("❌ No passwords found");
         return;
     }
 
-    println!("{}", format_results_table(results, profile_name));
+    println!("{}", format_results_table(results, profile_name, columns));
+}
+
+/// Write a file readable only by the current user (0600), atomically
+///
+/// The content goes to a temporary file next to `path` which is then renamed,
+/// so a partially written export is never left behind.
+pub fn write_private_file(path: &Path, content: &[u8]) -> Result<(), DecryptError> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| DecryptError::IoError(format!("Invalid path: {}", path.display())))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".tmp{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let _ = std::fs::remove_file(&temp_path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::LoginRow;
+
+    #[test]
+    fn test_format_skipped_table() {
+        let skipped = |url: &str, outcome| SkippedLogin {
+            browser: crate::browser::Browser::Chrome,
+            profile: "Default".to_string(),
+            row: LoginRow {
+                info: LoginInfo {
+                    url: url.to_string(),
+                    ..Default::default()
+                },
+                outcome,
+            },
+        };
+        let table = format_skipped_table(&[
+            skipped(
+                "https://a.example/",
+                RowOutcome::Undecryptable {
+                    reason: "wrong key".to_string(),
+                    version_prefix: Some("v11".to_string()),
+                    ciphertext_len: 48,
+                },
+            ),
+            skipped("https://b.example/", RowOutcome::NeverSave),
+        ]);
+        let cells = |url: &str| -> Vec<String> {
+            let line = table.lines().find(|line| line.contains(url)).unwrap();
+            let line = line.trim_matches('│');
+            line.split('┆')
+                .map(|cell| cell.trim().to_string())
+                .collect()
+        };
+        assert_eq!(cells("a.example")[5..], ["v11", "48", "wrong key"]);
+        assert_eq!(cells("b.example")[5..], ["", "", ""]);
+    }
+
+    #[test]
+    fn test_format_logins_json_selects_columns() {
+        let logins = vec![LoginInfoWithProfile {
+            browser: crate::browser::Browser::Brave,
+            profile: "Default".to_string(),
+            info: LoginInfo {
+                url: "https://example.com/".to_string(),
+                username: "alice".to_string(),
+                password: "secret".to_string(),
+                times_used: 3,
+                ..Default::default()
+            },
+        }];
+
+        let json = format_logins_json(&logins, Some(&[Column::Url, Column::TimesUsed])).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([{
+                "browser": "brave",
+                "profile": "Default",
+                "url": "https://example.com/",
+                "times_used": 3
+            }])
+        );
+    }
 }
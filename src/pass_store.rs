@@ -0,0 +1,420 @@
+//! `pass` / `gopass` password store export
+//!
+//! One encrypted file per login at `[<profile>/]<domain>/<username>.gpg`
+//! (or `.age`), containing the password on the first line followed by
+//! `url:` and `username:` lines and the note. Recipients come from the
+//! nearest `.gpg-id` (pass, gopass GPG backend) or `.age-recipients` (gopass
+//! age backend) between the entry's directory and the store root.
+//! Plaintext is only passed to `gpg` through a pipe, never written to disk.
+
+use crate::chrome_csv::display_name;
+use crate::encrypted_output::age_encrypt;
+use crate::export::{login_key, profile_folder_name};
+use crate::models::{DecryptError, LoginInfoWithProfile};
+use crate::output::write_private_file;
+use std::collections::{HashMap, HashSet};
+use std::fs::DirBuilder;
+use std::io::Write;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// GPG recipients file of pass
+pub const GPG_ID_FILE: &str = ".gpg-id";
+
+/// age recipients file of gopass
+pub const AGE_RECIPIENTS_FILE: &str = ".age-recipients";
+
+/// One entry of the store, before encryption
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassEntry {
+    /// Path relative to the store root, without extension
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// How the entries of one directory are encrypted
+#[derive(Debug, Clone)]
+enum Backend {
+    /// Key ids / emails from `.gpg-id`
+    Gpg(Vec<String>),
+    Age(Vec<age::x25519::Recipient>),
+}
+
+impl Backend {
+    fn extension(&self) -> &'static str {
+        match self {
+            Backend::Gpg(_) => "gpg",
+            Backend::Age(_) => "age",
+        }
+    }
+
+    fn encrypt(&self, plaintext: &str) -> Result<Vec<u8>, DecryptError> {
+        match self {
+            Backend::Gpg(recipients) => gpg_encrypt(recipients, plaintext.as_bytes()),
+            Backend::Age(recipients) => age_encrypt(recipients, plaintext.as_bytes()),
+        }
+    }
+}
+
+/// Lay out logins as store entries
+///
+/// Entries whose paths collide (e.g. `www.example.com` and `example.com`
+/// with the same username, or the same login in several profiles without
+/// profile folders) get `-2`, `-3`, ... suffixes. Logins are ordered by
+/// browser, profile, URL, username and creation time, then by their full
+/// export key and password, so the result does not depend on the order of
+/// `logins`.
+pub fn pass_entries(logins: &[LoginInfoWithProfile], folders: bool) -> Vec<PassEntry> {
+    let mut sorted: Vec<&LoginInfoWithProfile> = logins.iter().collect();
+    sorted.sort_by_cached_key(|login| {
+        (
+            login.browser.slug(),
+            &login.profile,
+            &login.info.url,
+            &login.info.username,
+            login.info.date_created,
+            login_key(login),
+            &login.info.password,
+        )
+    });
+
+    let mut used = HashSet::new();
+    let mut entries = Vec::new();
+    for login in sorted {
+        let mut dir = PathBuf::new();
+        if folders {
+            dir.push(path_component(
+                &profile_folder_name(login.browser, &login.profile),
+                "profile",
+            ));
+        }
+        dir.push(path_component(&display_name(&login.info.url), "unknown"));
+        let name = path_component(&login.info.username, "login");
+
+        let mut path = dir.join(&name);
+        let mut n = 2;
+        while !used.insert(path.clone()) {
+            path = dir.join(format!("{}-{}", name, n));
+            n += 1;
+        }
+
+        let mut content = format!(
+            "{}\nurl: {}\nusername: {}\n",
+            login.info.password, login.info.url, login.info.username
+        );
+        if !login.info.note.is_empty() {
+            content.push_str(&login.info.note);
+            content.push('\n');
+        }
+        entries.push(PassEntry { path, content });
+    }
+    entries
+}
+
+/// Write logins into the password store at `store`, encrypted to the
+/// recipients configured there; returns the number of entries written
+///
+/// Existing entries at the same paths are replaced.
+pub fn export_pass_store(
+    logins: &[LoginInfoWithProfile],
+    store: &Path,
+    folders: bool,
+) -> Result<usize, DecryptError> {
+    let entries = pass_entries(logins, folders);
+    let mut backends: HashMap<PathBuf, Backend> = HashMap::new();
+
+    for entry in &entries {
+        let dir = store.join(entry.path.parent().unwrap_or(Path::new("")));
+        let backend = match backends.get(&dir) {
+            Some(backend) => backend.clone(),
+            None => {
+                let backend = find_backend(store, &dir)?;
+                backends.insert(dir.clone(), backend.clone());
+                backend
+            }
+        };
+
+        DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+        let ciphertext = backend.encrypt(&entry.content)?;
+        let mut file_name = entry.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".");
+        file_name.push(backend.extension());
+        write_private_file(&dir.join(file_name), &ciphertext)?;
+    }
+
+    Ok(entries.len())
+}
+
+/// Recipients of `dir`: the nearest recipients file up to the store root
+fn find_backend(store: &Path, dir: &Path) -> Result<Backend, DecryptError> {
+    for candidate in dir.ancestors() {
+        let age_file = candidate.join(AGE_RECIPIENTS_FILE);
+        if age_file.is_file() {
+            return Ok(Backend::Age(read_age_recipients(&age_file)?));
+        }
+        let gpg_file = candidate.join(GPG_ID_FILE);
+        if gpg_file.is_file() {
+            let ids = recipient_lines(&std::fs::read_to_string(&gpg_file)?);
+            if ids.is_empty() {
+                return Err(DecryptError::FormatError(format!(
+                    "{} lists no keys",
+                    gpg_file.display()
+                )));
+            }
+            return Ok(Backend::Gpg(ids));
+        }
+        if candidate == store {
+            break;
+        }
+    }
+
+    Err(DecryptError::FormatError(format!(
+        "No {} or {} in {} (run `pass init <gpg-id>` or `gopass init --crypto age` first)",
+        GPG_ID_FILE,
+        AGE_RECIPIENTS_FILE,
+        store.display()
+    )))
+}
+
+fn read_age_recipients(path: &Path) -> Result<Vec<age::x25519::Recipient>, DecryptError> {
+    let recipients = recipient_lines(&std::fs::read_to_string(path)?)
+        .iter()
+        .map(|line| {
+            age::x25519::Recipient::from_str(line).map_err(|e| {
+                DecryptError::FormatError(format!(
+                    "Unsupported recipient '{}' in {}: {}",
+                    line,
+                    path.display(),
+                    e
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if recipients.is_empty() {
+        return Err(DecryptError::FormatError(format!(
+            "{} lists no recipients",
+            path.display()
+        )));
+    }
+    Ok(recipients)
+}
+
+/// Non-empty lines without `#` comments
+fn recipient_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Encrypt with `gpg`, passing the plaintext through a pipe
+///
+/// GPG's own trust checks apply, as with `pass`: a recipient key that is not
+/// valid in the keyring makes the export fail.
+fn gpg_encrypt(recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    let mut command = Command::new("gpg");
+    command.args(["--batch", "--yes", "--quiet", "--encrypt", "--output", "-"]);
+    for recipient in recipients {
+        command.args(["--recipient", recipient]);
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| DecryptError::CryptoError(format!("Cannot run gpg: {}", e)))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let output = std::thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(plaintext));
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(DecryptError::CryptoError(format!(
+            "gpg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// A single safe path component: no separators, control characters or
+/// leading dots (hidden files, `..`)
+fn path_component(text: &str, fallback: &str) -> String {
+    let cleaned: String = text
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.');
+    if cleaned.is_empty() {
+        fallback.to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::Browser;
+    use crate::models::PasswordStore;
+    use crate::test_support::TestProfileLogin;
+    use std::io::Read;
+
+    fn paths(entries: &[PassEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| e.path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_entry_paths_and_collisions() {
+        let logins = vec![
+            TestProfileLogin::new("https://example.com/", "alice", "pw-https://example.com/")
+                .profile("Profile 1")
+                .build(),
+            TestProfileLogin::new(
+                "https://www.example.com/login",
+                "alice",
+                "pw-https://www.example.com/login",
+            )
+            .build(),
+            TestProfileLogin::new("https://example.com/", "alice", "pw-https://example.com/")
+                .build(),
+            TestProfileLogin::new("https://a.test/", "../etc/passwd", "pw-https://a.test/")
+                .browser(Browser::Brave)
+                .build(),
+            TestProfileLogin::new("https://a.test/", "", "pw-https://a.test/").build(),
+        ];
+
+        let flat = pass_entries(&logins, false);
+        assert_eq!(
+            paths(&flat),
+            [
+                "a.test/_etc_passwd",
+                "a.test/login",
+                "example.com/alice",
+                "example.com/alice-2",
+                "example.com/alice-3",
+            ]
+        );
+        // Same layout whatever the input order
+        let mut reversed = logins.clone();
+        reversed.reverse();
+        assert_eq!(pass_entries(&reversed, false), flat);
+
+        let nested = pass_entries(&logins, true);
+        assert_eq!(
+            paths(&nested),
+            [
+                "Default (Brave)/a.test/_etc_passwd",
+                "Default/a.test/login",
+                "Default/example.com/alice",
+                "Default/example.com/alice-2",
+                "Profile 1/example.com/alice",
+            ]
+        );
+        assert_eq!(
+            nested[2].content,
+            "pw-https://example.com/\nurl: https://example.com/\nusername: alice\n"
+        );
+    }
+
+    #[test]
+    fn test_entry_paths_of_tied_logins() {
+        // Same URL, username and creation time, told apart only by Chrome's
+        // unique key or the password
+        let login = TestProfileLogin::new("https://example.com/", "alice", "pw").build();
+        let mut account = login.clone();
+        account.info.password = "pw-account".to_string();
+        account.info.store = PasswordStore::Account;
+        let mut realm = login.clone();
+        realm.info.password = "pw-realm".to_string();
+        realm.info.signon_realm = "https://example.com/".to_string();
+        let mut form = login.clone();
+        form.info.password = "pw-form".to_string();
+        form.info.password_element = "pin".to_string();
+        let mut password = login.clone();
+        password.info.password = "pw-other".to_string();
+        let logins = vec![login, account, realm, form, password];
+
+        let expected = pass_entries(&logins, false);
+        for rotation in 0..logins.len() {
+            let mut shuffled = logins.clone();
+            shuffled.rotate_left(rotation);
+            assert_eq!(pass_entries(&shuffled, false), expected);
+            shuffled.reverse();
+            assert_eq!(pass_entries(&shuffled, false), expected);
+        }
+    }
+
+    #[test]
+    fn test_export_age_store_uses_nearest_recipients() {
+        let store = tempfile::tempdir().unwrap();
+        let personal = age::x25519::Identity::generate();
+        let work = age::x25519::Identity::generate();
+        std::fs::write(
+            store.path().join(AGE_RECIPIENTS_FILE),
+            format!("# personal\n{}\n", personal.to_public()),
+        )
+        .unwrap();
+        std::fs::create_dir(store.path().join("Profile 1")).unwrap();
+        std::fs::write(
+            store.path().join("Profile 1").join(AGE_RECIPIENTS_FILE),
+            work.to_public().to_string(),
+        )
+        .unwrap();
+
+        let logins = vec![
+            TestProfileLogin::new("https://example.com/", "alice", "pw-https://example.com/")
+                .note("pin: 1234")
+                .build(),
+            TestProfileLogin::new("https://corp.test/", "bob", "pw-https://corp.test/")
+                .profile("Profile 1")
+                .build(),
+        ];
+        assert_eq!(export_pass_store(&logins, store.path(), true).unwrap(), 2);
+
+        let decrypt = |path: &Path, identity: &age::x25519::Identity| {
+            let ciphertext = std::fs::read(path).unwrap();
+            let decryptor = age::Decryptor::new(&ciphertext[..]).unwrap();
+            let mut reader = decryptor
+                .decrypt(std::iter::once(identity as &dyn age::Identity))
+                .ok()?;
+            let mut plaintext = String::new();
+            reader.read_to_string(&mut plaintext).unwrap();
+            Some(plaintext)
+        };
+        let default_entry = store.path().join("Default/example.com/alice.age");
+        assert_eq!(
+            decrypt(&default_entry, &personal).unwrap(),
+            "pw-https://example.com/\nurl: https://example.com/\nusername: alice\npin: 1234\n"
+        );
+        let work_entry = store.path().join("Profile 1/corp.test/bob.age");
+        assert!(decrypt(&work_entry, &personal).is_none());
+        assert!(
+            decrypt(&work_entry, &work)
+                .unwrap()
+                .starts_with("pw-https://corp.test/\n")
+        );
+    }
+
+    #[test]
+    fn test_export_without_recipients_fails() {
+        let store = tempfile::tempdir().unwrap();
+        let logins = vec![TestProfileLogin::new("https://a.test/", "a", "pw").build()];
+        assert!(matches!(
+            export_pass_store(&logins, store.path(), false),
+            Err(DecryptError::FormatError(_))
+        ));
+        assert_eq!(std::fs::read_dir(store.path()).unwrap().count(), 0);
+    }
+}
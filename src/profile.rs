@@ -1,50 +1,66 @@
+use crate::browser::Browser;
 pub use crate::models::ChromeProfile;
+use crate::models::{DecryptError, PasswordStore};
+use chrono::DateTime;
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::{info, warn};
 
-/// Get Chrome data directory
-fn get_chrome_data_dir() -> Option<PathBuf> {
-    #[cfg(target_os = "macos")]
-    {
-        dirs::home_dir().map(|home| home.join("Library/Application Support/Google/Chrome"))
-    }
-
-    #[cfg(not(target_os = "macos"))]
-    {
-        // Linux: ~/.config/google-chrome
-        // Windows: %LOCALAPPDATA%\Google\Chrome\User Data
-        dirs::config_dir().map(|dir| dir.join("google-chrome"))
-    }
-}
-
 /// Find all Chrome profiles
-///
-/// Chrome profile directory structure:
-/// - Default (default profile)
-/// - Profile 1, Profile 2, ... (other profiles)
 pub fn find_chrome_profiles() -> Vec<ChromeProfile> {
-    info!("Searching for Chrome profiles");
-    let mut profiles = Vec::new();
+    find_browser_profiles(Browser::Chrome)
+}
+
+/// Find all profiles of a browser in its default data directory
+pub fn find_browser_profiles(browser: Browser) -> Vec<ChromeProfile> {
+    info!("Searching for {} profiles", browser);
 
-    let chrome_dir = match get_chrome_data_dir() {
+    let chrome_dir = match browser.data_dir() {
         Some(dir) => dir,
         None => {
-            warn!("Could not determine Chrome data directory");
-            return profiles;
+            info!("No {} data directory on this system", browser);
+            return Vec::new();
         }
     };
 
     if !chrome_dir.exists() {
-        warn!(
-            "Chrome data directory does not exist: {}",
+        // Most users only have some of the supported browsers
+        info!(
+            "{} data directory does not exist: {}",
+            browser,
             chrome_dir.display()
         );
-        return profiles;
+        return Vec::new();
+    }
+
+    let profiles = find_profiles_in_dir(browser, &chrome_dir);
+    info!("Found {} {} profile(s)", profiles.len(), browser);
+    profiles
+}
+
+/// Find all profiles in a user data directory
+///
+/// Chrome profile directory structure:
+/// - Local State (profile names and accounts)
+/// - Default (default profile)
+/// - Profile 1, Profile 2, ... (other profiles)
+///
+/// Opera keeps its only profile in the data directory itself; it is
+/// reported as "Default".
+pub fn find_profiles_in_dir(browser: Browser, chrome_dir: &Path) -> Vec<ChromeProfile> {
+    let mut profiles = Vec::new();
+    let mut info_cache = read_profile_info_cache(chrome_dir);
+
+    if browser == Browser::Opera {
+        let profile = ChromeProfile::new(browser, "Default", chrome_dir.to_path_buf());
+        if profile.has_login_data() {
+            profiles.push(profile);
+        }
     }
 
     // Iterate through subdirectories under Chrome directory
-    let entries = match fs::read_dir(&chrome_dir) {
+    let entries = match fs::read_dir(chrome_dir) {
         Ok(entries) => entries,
         Err(_) => return profiles,
     };
@@ -58,7 +74,7 @@ pub fn find_chrome_profiles() -> Vec<ChromeProfile> {
         }
 
         let profile_name = match path.file_name().and_then(|n| n.to_str()) {
-            Some(name) => name,
+            Some(name) => name.to_string(),
             None => continue,
         };
 
@@ -67,20 +83,18 @@ pub fn find_chrome_profiles() -> Vec<ChromeProfile> {
             continue;
         }
 
-        // Check if Login Data file exists
-        let login_data = path.join("Login Data");
-        if !login_data.exists() {
+        // Check if a Login Data (or Login Data For Account) file exists
+        let mut profile = ChromeProfile::new(browser, &profile_name, path);
+        if !profile.has_login_data() {
             continue;
         }
 
-        profiles.push(ChromeProfile {
-            name: profile_name.to_string(),
-            path,
-            login_data_path: login_data,
-        });
+        if let Some(info) = info_cache.remove(&profile_name) {
+            profile.apply_info(info);
+        }
+        profiles.push(profile);
     }
 
-    info!("Found {} Chrome profile(s)", profiles.len());
     // Sort by profile name (Default first)
     profiles.sort_by(|a, b| {
         if a.name == "Default" {
@@ -95,12 +109,349 @@ pub fn find_chrome_profiles() -> Vec<ChromeProfile> {
     profiles
 }
 
+/// Per-profile entry of `profile.info_cache` in `Local State`
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct ProfileInfo {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    user_name: Option<String>,
+    #[serde(default)]
+    avatar_icon: Option<String>,
+    /// Seconds since the Unix epoch
+    #[serde(default)]
+    active_time: Option<f64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct LocalState {
+    #[serde(default)]
+    profile: LocalStateProfiles,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct LocalStateProfiles {
+    #[serde(default)]
+    info_cache: HashMap<String, ProfileInfo>,
+}
+
+/// Read `profile.info_cache` from `Local State`, keyed by directory name
+///
+/// A missing or unreadable file only means profiles are shown without
+/// display names.
+fn read_profile_info_cache(chrome_dir: &Path) -> HashMap<String, ProfileInfo> {
+    let path = chrome_dir.join("Local State");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return HashMap::new(),
+    };
+
+    match serde_json::from_str::<LocalState>(&content) {
+        Ok(state) => state.profile.info_cache,
+        Err(e) => {
+            warn!("Ignoring unreadable {}: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+impl ChromeProfile {
+    /// Profile stored in `path`, without `Local State` details
+    pub fn new(browser: Browser, name: &str, path: PathBuf) -> Self {
+        let existing = |store: PasswordStore| {
+            let db_path = path.join(store.file_name());
+            db_path.is_file().then_some(db_path)
+        };
+        Self {
+            browser,
+            name: name.to_string(),
+            login_data_path: existing(PasswordStore::Local),
+            account_login_data_path: existing(PasswordStore::Account),
+            path,
+            display_name: None,
+            email: None,
+            avatar_icon: None,
+            last_active: None,
+        }
+    }
+
+    /// Profile directory outside the default location (a backup, a portable
+    /// install, ...), named after the directory
+    pub fn from_dir(browser: Browser, path: &Path) -> Result<Self, DecryptError> {
+        let profile = Self::new(browser, &dir_label(path), path.to_path_buf());
+        if !profile.has_login_data() {
+            return Err(DecryptError::ProfileNotFound(format!(
+                "no Login Data in {}",
+                path.display()
+            )));
+        }
+        Ok(profile)
+    }
+
+    /// A single `Login Data` file, named after the directory containing it
+    ///
+    /// A file called `Login Data For Account` is read as the account store,
+    /// any other name as the local store.
+    pub fn from_login_data(browser: Browser, login_data: &Path) -> Result<Self, DecryptError> {
+        if !login_data.is_file() {
+            return Err(DecryptError::LoginDataNotFound);
+        }
+        let dir = login_data
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let mut profile = Self::new(browser, &dir_label(dir), dir.to_path_buf());
+        let is_account = login_data
+            .file_name()
+            .is_some_and(|name| name == PasswordStore::Account.file_name());
+        if is_account {
+            profile.login_data_path = None;
+            profile.account_login_data_path = Some(login_data.to_path_buf());
+        } else {
+            profile.login_data_path = Some(login_data.to_path_buf());
+            profile.account_login_data_path = None;
+        }
+        Ok(profile)
+    }
+
+    /// Password databases of this profile that exist on disk
+    pub fn databases(&self) -> Vec<(PasswordStore, &Path)> {
+        [
+            (PasswordStore::Local, &self.login_data_path),
+            (PasswordStore::Account, &self.account_login_data_path),
+        ]
+        .into_iter()
+        .filter_map(|(store, path)| path.as_deref().map(|path| (store, path)))
+        .collect()
+    }
+
+    /// Whether the profile has at least one password database
+    pub fn has_login_data(&self) -> bool {
+        self.login_data_path.is_some() || self.account_login_data_path.is_some()
+    }
+
+    pub(crate) fn apply_info(&mut self, info: ProfileInfo) {
+        let non_empty = |value: Option<String>| value.filter(|v| !v.is_empty());
+        self.display_name = non_empty(info.name);
+        self.email = non_empty(info.user_name);
+        self.avatar_icon = non_empty(info.avatar_icon);
+        self.last_active = info
+            .active_time
+            .filter(|t| *t > 0.0)
+            .and_then(|t| DateTime::from_timestamp_millis((t * 1000.0) as i64));
+    }
+
+    /// Whether `query` names this profile: its directory name, or
+    /// (case-insensitively) its display name or signed-in email
+    pub fn matches(&self, query: &str) -> bool {
+        let matches_ignore_case = |value: &Option<String>| {
+            value
+                .as_ref()
+                .is_some_and(|v| v.eq_ignore_ascii_case(query))
+        };
+        self.name == query
+            || matches_ignore_case(&self.display_name)
+            || matches_ignore_case(&self.email)
+    }
+}
+
+/// Profiles of a user data directory given on the command line
+///
+/// Accepts a user data directory (as passed to `chrome --user-data-dir`) or a
+/// single profile directory.
+pub fn find_profiles_in_user_data_dir(
+    browser: Browser,
+    dir: &Path,
+) -> Result<Vec<ChromeProfile>, DecryptError> {
+    if !dir.is_dir() {
+        return Err(DecryptError::ProfileNotFound(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+
+    let profiles = find_profiles_in_dir(browser, dir);
+    if profiles.is_empty() {
+        let profile = ChromeProfile::new(browser, &dir_label(dir), dir.to_path_buf());
+        if profile.has_login_data() {
+            return Ok(vec![profile]);
+        }
+    }
+    Ok(profiles)
+}
+
+/// Last path component, for naming profiles outside the default location
+fn dir_label(dir: &Path) -> String {
+    std::fs::canonicalize(dir)
+        .ok()
+        .as_deref()
+        .unwrap_or(dir)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| dir.display().to_string())
+}
+
 /// Find a specific Chrome profile by name
-pub fn find_profile_by_name(name: &str) -> Option<ChromeProfile> {
-    find_chrome_profiles().into_iter().find(|p| p.name == name)
+pub fn find_profile_by_name(name: &str) -> Result<Option<ChromeProfile>, DecryptError> {
+    find_browser_profile_by_name(Browser::Chrome, name)
+}
+
+/// Find a specific profile of a browser by directory name, display name or
+/// email (an exact directory name wins)
+pub fn find_browser_profile_by_name(
+    browser: Browser,
+    name: &str,
+) -> Result<Option<ChromeProfile>, DecryptError> {
+    select_profile(find_browser_profiles(browser), name)
+}
+
+/// Pick a profile by directory name, display name or email from a list (an
+/// exact directory name wins)
+///
+/// Display names are not unique, so a name matching several profiles is an
+/// error listing their directory names.
+pub fn select_profile(
+    profiles: Vec<ChromeProfile>,
+    name: &str,
+) -> Result<Option<ChromeProfile>, DecryptError> {
+    if let Some(index) = profiles.iter().position(|p| p.name == name) {
+        return Ok(profiles.into_iter().nth(index));
+    }
+    let mut matching: Vec<_> = profiles.into_iter().filter(|p| p.matches(name)).collect();
+    if matching.len() > 1 {
+        let dirs: Vec<_> = matching.iter().map(|p| p.name.as_str()).collect();
+        return Err(DecryptError::AmbiguousProfile(format!(
+            "'{}' matches {}; select one by directory name",
+            name,
+            dirs.join(", ")
+        )));
+    }
+    Ok(matching.pop())
 }
 
 /// Get the default Chrome profile
 pub fn get_default_profile() -> Option<ChromeProfile> {
-    find_profile_by_name("Default")
+    find_chrome_profiles()
+        .into_iter()
+        .find(|p| p.name == "Default")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_with_local_state() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Default", "Profile 3", "System Profile"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        for name in ["Default", "Profile 3"] {
+            fs::write(dir.path().join(name).join("Login Data"), b"").unwrap();
+        }
+        fs::write(
+            dir.path().join("Local State"),
+            r#"{"profile": {"info_cache": {
+                "Default": {"name": "Person 1", "user_name": ""},
+                "Profile 3": {"name": "Work", "user_name": "alice@corp.example",
+                              "avatar_icon": "chrome://theme/IDR_PROFILE_AVATAR_26",
+                              "active_time": 1704067200.5}
+            }}, "other": 1}"#,
+        )
+        .unwrap();
+
+        let profiles = find_profiles_in_dir(Browser::Chrome, dir.path());
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "Default");
+        assert_eq!(profiles[0].display_name.as_deref(), Some("Person 1"));
+        assert_eq!(profiles[0].email, None);
+
+        let work = &profiles[1];
+        assert_eq!(work.display_name.as_deref(), Some("Work"));
+        assert_eq!(work.email.as_deref(), Some("alice@corp.example"));
+        assert_eq!(
+            work.last_active.unwrap().to_rfc3339(),
+            "2024-01-01T00:00:00.500+00:00"
+        );
+        assert!(work.matches("Profile 3"));
+        assert!(work.matches("work"));
+        assert!(work.matches("Alice@corp.example"));
+        assert!(!work.matches("profile 3"));
+    }
+
+    #[test]
+    fn test_select_profile_by_ambiguous_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Default", "Profile 1", "Profile 2"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+            fs::write(dir.path().join(name).join("Login Data"), b"").unwrap();
+        }
+        fs::write(
+            dir.path().join("Local State"),
+            r#"{"profile": {"info_cache": {
+                "Default": {"name": "Personal"},
+                "Profile 1": {"name": "Work"},
+                "Profile 2": {"name": "work"}
+            }}}"#,
+        )
+        .unwrap();
+        let profiles = || find_profiles_in_dir(Browser::Chrome, dir.path());
+
+        let personal = select_profile(profiles(), "personal").unwrap().unwrap();
+        assert_eq!(personal.name, "Default");
+        assert!(select_profile(profiles(), "Home").unwrap().is_none());
+        let profile_2 = select_profile(profiles(), "Profile 2").unwrap().unwrap();
+        assert_eq!(profile_2.name, "Profile 2");
+
+        let err = select_profile(profiles(), "Work").unwrap_err();
+        assert!(matches!(err, DecryptError::AmbiguousProfile(_)));
+        assert!(err.to_string().contains("Profile 1, Profile 2"));
+    }
+
+    #[test]
+    fn test_profile_with_account_store_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile_dir = dir.path().join("Profile 2");
+        fs::create_dir(&profile_dir).unwrap();
+        fs::write(profile_dir.join("Login Data For Account"), b"").unwrap();
+
+        let profiles = find_profiles_in_dir(Browser::Chrome, dir.path());
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].login_data_path, None);
+        assert_eq!(
+            profiles[0].databases(),
+            vec![(
+                PasswordStore::Account,
+                profile_dir.join("Login Data For Account").as_path()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_profiles_outside_default_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile_dir = dir.path().join("restored");
+        fs::create_dir(&profile_dir).unwrap();
+        fs::write(profile_dir.join("Login Data"), b"").unwrap();
+
+        // A single profile directory passed as --user-data-dir
+        let profiles = find_profiles_in_user_data_dir(Browser::Chrome, &profile_dir).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "restored");
+
+        // A user data directory containing it
+        let profiles = find_profiles_in_user_data_dir(Browser::Brave, dir.path()).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].browser, Browser::Brave);
+        assert_eq!(profiles[0].path, profile_dir);
+
+        let copy = dir.path().join("Login Data.bak");
+        fs::write(&copy, b"").unwrap();
+        let profile = ChromeProfile::from_login_data(Browser::Chrome, &copy).unwrap();
+        assert_eq!(profile.login_data_path, Some(copy.clone()));
+        assert!(
+            ChromeProfile::from_login_data(Browser::Chrome, &dir.path().join("missing")).is_err()
+        );
+        assert!(find_profiles_in_user_data_dir(Browser::Chrome, &copy).is_err());
+    }
 }
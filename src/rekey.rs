@@ -0,0 +1,251 @@
+//! Re-encrypt a `Login Data` database under a new key
+//!
+//! Needed when the "Chrome Safe Storage" secret changes (e.g. a regenerated
+//! macOS Keychain entry after a migration) and the old passwords become
+//! unreadable. Passwords and notes are decrypted with the old key and
+//! encrypted with the new one in a copy, which then replaces the original;
+//! the original is kept as a backup.
+
+use crate::crypto::{DerivedKeys, decrypt_password, encrypt_password};
+use crate::database::{backup_database, has_table};
+use crate::import::profile_lock;
+use crate::models::DecryptError;
+use chrono::Utc;
+use rusqlite::{Connection, OpenFlags};
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// Outcome of re-encrypting one database
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct RekeySummary {
+    /// Passwords re-encrypted with the new key
+    pub passwords: usize,
+    /// Notes re-encrypted with the new key
+    pub notes: usize,
+    /// Values that already decrypt with the new key (e.g. saved after the
+    /// key changed), left as they are
+    pub unchanged: usize,
+    /// Copy of the database before re-encryption
+    pub backup: PathBuf,
+}
+
+/// Re-encrypt every password and note of the database at `db_path`
+///
+/// Fails without touching the database if the browser is running or a
+/// value decrypts with neither key. Once the re-encrypted copy is complete,
+/// the original is backed up as `<name>.pre-rekey-<timestamp>` and the copy
+/// renamed over it.
+pub fn rekey_database(
+    db_path: &Path,
+    old: &DerivedKeys,
+    new: &DerivedKeys,
+) -> Result<RekeySummary, DecryptError> {
+    if let Some(lock) = profile_lock(db_path) {
+        return Err(DecryptError::DatabaseError(format!(
+            "The browser is running ({} exists), quit it before re-encrypting",
+            lock.display()
+        )));
+    }
+    if !db_path.is_file() {
+        return Err(DecryptError::LoginDataNotFound);
+    }
+
+    // Fold a leftover WAL into the database, so no side file outlives the swap
+    {
+        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    }
+
+    let temp_path = sibling(db_path, ".", &format!(".rekey{}", std::process::id()));
+    let backup = sibling(
+        db_path,
+        "",
+        &format!(".pre-rekey-{}", Utc::now().format("%Y%m%d%H%M%S")),
+    );
+    let result = (|| {
+        create_private_file(&temp_path)?;
+        backup_database(db_path, &temp_path)?;
+        let (passwords, notes, unchanged) = reencrypt(&temp_path, old, new)?;
+
+        create_private_file(&backup)?;
+        backup_database(db_path, &backup)?;
+        std::fs::rename(&temp_path, db_path)?;
+        Ok(RekeySummary {
+            passwords,
+            notes,
+            unchanged,
+            backup: backup.clone(),
+        })
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Re-encrypt the copy at `path` in one transaction; returns the number of
+/// passwords, notes and unchanged values
+fn reencrypt(
+    path: &Path,
+    old: &DerivedKeys,
+    new: &DerivedKeys,
+) -> Result<(usize, usize, usize), DecryptError> {
+    let mut conn = Connection::open(path)?;
+    let tx = conn.transaction()?;
+    let mut unchanged = 0;
+    let mut failed = 0;
+
+    let mut tables = vec![("logins", "password_value")];
+    if has_table(&tx, "password_notes")? {
+        tables.push(("password_notes", "value"));
+    }
+
+    let mut counts = Vec::new();
+    for (table, column) in tables {
+        let rows = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT rowid, {column} FROM {table} WHERE length({column}) > 0"
+            ))?;
+            stmt.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut count = 0;
+        for (rowid, blob) in rows {
+            match decrypt_password(old, &blob) {
+                Ok(plaintext) => {
+                    tx.execute(
+                        &format!("UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"),
+                        rusqlite::params![encrypt_password(new, &plaintext)?, rowid],
+                    )?;
+                    count += 1;
+                }
+                Err(_) if decrypt_password(new, &blob).is_ok() => unchanged += 1,
+                Err(e) => {
+                    tracing::debug!("{} row {} decrypts with neither key: {}", table, rowid, e);
+                    failed += 1;
+                }
+            }
+        }
+        counts.push(count);
+    }
+
+    if failed > 0 {
+        return Err(DecryptError::CryptoError(format!(
+            "{} value(s) decrypt with neither the old nor the new key, nothing was changed",
+            failed
+        )));
+    }
+    tx.commit()?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+
+    Ok((counts[0], counts.get(1).copied().unwrap_or(0), unchanged))
+}
+
+/// `path` with its file name wrapped in `prefix` and `suffix`
+fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(prefix);
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Create an empty file readable only by the current user
+fn create_private_file(path: &Path) -> Result<(), DecryptError> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{SnapshotMode, query_login_data};
+    use crate::models::QueryParams;
+    use crate::test_support::{TestLogin, create_login_db, insert_login};
+
+    fn passwords_and_notes(db_path: &Path, keys: &DerivedKeys) -> Vec<(String, String)> {
+        let params = QueryParams {
+            keyword: None,
+            derived_keys: keys,
+            snapshot: SnapshotMode::Backup,
+        };
+        query_login_data(db_path, &params)
+            .unwrap()
+            .into_iter()
+            .map(|info| (info.password, info.note))
+            .collect()
+    }
+
+    #[test]
+    fn test_rekey_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let old = DerivedKeys::for_macos("old-keychain-secret");
+        let new = DerivedKeys::for_macos("new-keychain-secret");
+        create_login_db(
+            &db_path,
+            &old,
+            &[
+                TestLogin::new("https://a.example/", "alice", "pw-a").note("pin: 1234"),
+                TestLogin::new("https://b.example/", "bob", "pw-b"),
+            ],
+        );
+        // Saved by the browser after the key changed
+        let conn = Connection::open(&db_path).unwrap();
+        insert_login(
+            &conn,
+            &new,
+            &TestLogin::new("https://c.example/", "carol", "pw-c"),
+        );
+        drop(conn);
+
+        let summary = rekey_database(&db_path, &old, &new).unwrap();
+        assert_eq!(
+            (summary.passwords, summary.notes, summary.unchanged),
+            (2, 1, 1)
+        );
+        assert_eq!(
+            passwords_and_notes(&db_path, &new),
+            [
+                ("pw-a".to_string(), "pin: 1234".to_string()),
+                ("pw-b".to_string(), String::new()),
+                ("pw-c".to_string(), String::new()),
+            ]
+        );
+        assert_eq!(passwords_and_notes(&summary.backup, &old).len(), 2);
+
+        let files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files.len(), 2, "unexpected files: {:?}", files);
+    }
+
+    #[test]
+    fn test_rekey_with_wrong_old_key_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("Login Data");
+        let old = DerivedKeys::for_macos("old-keychain-secret");
+        create_login_db(
+            &db_path,
+            &old,
+            &[TestLogin::new("https://a.example/", "alice", "pw-a")],
+        );
+        let before = std::fs::read(&db_path).unwrap();
+
+        let wrong = DerivedKeys::for_macos("wrong");
+        let new = DerivedKeys::for_macos("new-keychain-secret");
+        assert!(rekey_database(&db_path, &wrong, &new).is_err());
+        assert_eq!(std::fs::read(&db_path).unwrap(), before);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
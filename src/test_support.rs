@@ -0,0 +1,239 @@
+//! Helpers for building synthetic Chrome databases in tests
+
+use crate::browser::Browser;
+use crate::crypto::{DerivedKeys, IV};
+use crate::models::{LoginInfo, LoginInfoWithProfile};
+use aes::Aes128;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockEncryptMut, KeyIvInit};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use std::path::Path;
+
+/// `logins` table as created by current Chrome versions
+pub const LOGINS_SCHEMA: &str = "CREATE TABLE logins (
+    origin_url VARCHAR NOT NULL,
+    action_url VARCHAR,
+    username_element VARCHAR,
+    username_value VARCHAR,
+    password_element VARCHAR,
+    password_value BLOB,
+    submit_element VARCHAR,
+    signon_realm VARCHAR NOT NULL,
+    date_created INTEGER NOT NULL,
+    blacklisted_by_user INTEGER NOT NULL,
+    scheme INTEGER NOT NULL,
+    password_type INTEGER,
+    times_used INTEGER,
+    form_data BLOB,
+    display_name VARCHAR,
+    icon_url VARCHAR,
+    federation_url VARCHAR,
+    skip_zero_click INTEGER,
+    generation_upload_status INTEGER,
+    possible_username_pairs BLOB,
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date_last_used INTEGER NOT NULL DEFAULT 0,
+    moving_blocked_for BLOB,
+    date_password_modified INTEGER NOT NULL DEFAULT 0,
+    sender_email VARCHAR,
+    sender_name VARCHAR,
+    date_received INTEGER,
+    sharing_notification_displayed INTEGER NOT NULL DEFAULT 0,
+    keychain_identifier BLOB,
+    sender_profile_image_url VARCHAR,
+    UNIQUE (origin_url, username_element, username_value, password_element, signon_realm)
+)";
+
+/// `password_notes` table as created by current Chrome versions
+pub const PASSWORD_NOTES_SCHEMA: &str = "CREATE TABLE password_notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    parent_id INTEGER NOT NULL REFERENCES logins ON UPDATE CASCADE ON DELETE CASCADE
+        DEFERRABLE INITIALLY DEFERRED,
+    key VARCHAR NOT NULL,
+    value BLOB,
+    date_created INTEGER NOT NULL,
+    confidential INTEGER,
+    UNIQUE (parent_id, key)
+)";
+
+/// `insecure_credentials` table (Password Checkup results) as created by current Chrome versions
+pub const INSECURE_CREDENTIALS_SCHEMA: &str = "CREATE TABLE insecure_credentials (
+    parent_id INTEGER REFERENCES logins ON UPDATE CASCADE ON DELETE CASCADE
+        DEFERRABLE INITIALLY DEFERRED,
+    insecurity_type INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    is_muted INTEGER NOT NULL DEFAULT 0,
+    trigger_notification_from_backend INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (parent_id, insecurity_type)
+)";
+
+/// Encrypt a plaintext the way Chrome does, prefixed with `version`
+pub fn encrypt(key: &[u8], version: &str, plaintext: &str) -> Vec<u8> {
+    let encryptor = cbc::Encryptor::<Aes128>::new(key.into(), IV.into());
+    let mut blob = version.as_bytes().to_vec();
+    blob.extend(encryptor.encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes()));
+    blob
+}
+
+/// A row to insert into a synthetic `logins` table
+#[derive(Debug, Clone)]
+pub struct TestLogin {
+    pub origin_url: String,
+    pub action_url: String,
+    pub signon_realm: String,
+    pub username: String,
+    pub password: String,
+    pub version: String,
+    pub date_created: i64,
+    pub date_password_modified: i64,
+    pub times_used: i64,
+    pub note: Option<String>,
+}
+
+impl TestLogin {
+    pub fn new(url: &str, username: &str, password: &str) -> Self {
+        Self {
+            origin_url: url.to_string(),
+            action_url: url.to_string(),
+            signon_realm: url.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            version: "v10".to_string(),
+            date_created: 0,
+            date_password_modified: 0,
+            times_used: 0,
+            note: None,
+        }
+    }
+
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    pub fn action_url(mut self, action_url: &str) -> Self {
+        self.action_url = action_url.to_string();
+        self
+    }
+
+    pub fn signon_realm(mut self, signon_realm: &str) -> Self {
+        self.signon_realm = signon_realm.to_string();
+        self
+    }
+
+    /// Set `date_created` and `date_password_modified` (WebKit microseconds)
+    pub fn dates(mut self, created: i64, password_modified: i64) -> Self {
+        self.date_created = created;
+        self.date_password_modified = password_modified;
+        self
+    }
+
+    pub fn times_used(mut self, times_used: i64) -> Self {
+        self.times_used = times_used;
+        self
+    }
+
+    /// Attach a note (stored encrypted in `password_notes`)
+    pub fn note(mut self, note: &str) -> Self {
+        self.note = Some(note.to_string());
+        self
+    }
+}
+
+/// A decrypted login of a profile, as the exporters and reports receive it
+#[derive(Debug, Clone)]
+pub struct TestProfileLogin(LoginInfoWithProfile);
+
+impl TestProfileLogin {
+    /// A login of Chrome's `Default` profile, saved for `url`
+    pub fn new(url: &str, username: &str, password: &str) -> Self {
+        Self(LoginInfoWithProfile {
+            browser: Browser::Chrome,
+            profile: "Default".to_string(),
+            info: LoginInfo {
+                url: url.to_string(),
+                username: username.to_string(),
+                password: password.to_string(),
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn browser(mut self, browser: Browser) -> Self {
+        self.0.browser = browser;
+        self
+    }
+
+    pub fn profile(mut self, profile: &str) -> Self {
+        self.0.profile = profile.to_string();
+        self
+    }
+
+    pub fn signon_realm(mut self, signon_realm: &str) -> Self {
+        self.0.info.signon_realm = signon_realm.to_string();
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Self {
+        self.0.info.note = note.to_string();
+        self
+    }
+
+    pub fn date_created(mut self, date_created: Option<DateTime<Utc>>) -> Self {
+        self.0.info.date_created = date_created;
+        self
+    }
+
+    pub fn date_password_modified(mut self, date_password_modified: Option<DateTime<Utc>>) -> Self {
+        self.0.info.date_password_modified = date_password_modified;
+        self
+    }
+
+    pub fn build(self) -> LoginInfoWithProfile {
+        self.0
+    }
+}
+
+/// Create a `Login Data` database at `path` holding `logins` encrypted with `keys`
+pub fn create_login_db(path: &Path, keys: &DerivedKeys, logins: &[TestLogin]) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute_batch(LOGINS_SCHEMA).unwrap();
+    conn.execute_batch(PASSWORD_NOTES_SCHEMA).unwrap();
+    conn.execute_batch(INSECURE_CREDENTIALS_SCHEMA).unwrap();
+
+    for login in logins {
+        insert_login(&conn, keys, login);
+    }
+}
+
+/// Insert one login, encrypted with `keys`
+pub fn insert_login(conn: &Connection, keys: &DerivedKeys, login: &TestLogin) {
+    let key = keys.key_for_version(&login.version).unwrap();
+    conn.execute(
+        "INSERT INTO logins (origin_url, action_url, signon_realm, username_value,
+            password_value, date_created, date_password_modified, times_used,
+            blacklisted_by_user, scheme)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0, 0)",
+        rusqlite::params![
+            login.origin_url,
+            login.action_url,
+            login.signon_realm,
+            login.username,
+            encrypt(key, &login.version, &login.password),
+            login.date_created,
+            login.date_password_modified,
+            login.times_used,
+        ],
+    )
+    .unwrap();
+
+    if let Some(note) = &login.note {
+        conn.execute(
+            "INSERT INTO password_notes (parent_id, key, value, date_created)
+             VALUES (?1, '', ?2, 0)",
+            rusqlite::params![conn.last_insert_rowid(), encrypt(key, &login.version, note)],
+        )
+        .unwrap();
+    }
+}